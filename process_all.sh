@@ -1,2 +0,0 @@
-rm ./script-gen/dist/*
-cargo run --release -- ./script-gen/dist/ ./script-gen/source/*
@@ -1,13 +1,46 @@
-use std::{cmp::Ordering, io::Write};
+use std::io::{self, Write};
 
 use crate::{
     character::{Character, Team},
+    config::Config,
+    markup::{escape, render, render_reminder},
     script::Script,
+    sheet::Paper,
 };
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct AlmanacFields {
     pub intro: Vec<String>,
+    pub difficulty: Option<String>,
+    /// Recommended player count, as the smallest and largest
+    pub players: Option<(usize, usize)>,
+    pub tags: Vec<String>,
+    pub themes: Vec<String>,
+    /// Designer notes
+    pub notes: Vec<String>,
+    pub changelog: Vec<String>,
+    /// Playtesting credits
+    pub playtesting: Vec<String>,
+}
+
+impl AlmanacFields {
+    /// Takes every field of a base script that is not set here
+    pub fn inherit(&mut self, base: AlmanacFields) {
+        for (field, base) in [
+            (&mut self.intro, base.intro),
+            (&mut self.tags, base.tags),
+            (&mut self.themes, base.themes),
+            (&mut self.notes, base.notes),
+            (&mut self.changelog, base.changelog),
+            (&mut self.playtesting, base.playtesting),
+        ] {
+            if field.is_empty() {
+                *field = base;
+            }
+        }
+        self.difficulty = self.difficulty.take().or(base.difficulty);
+        self.players = self.players.or(base.players);
+    }
 }
 
 const STYLE: &str = include_str!("style.css");
@@ -19,168 +52,524 @@ impl Script {
         writer: &mut T,
         first_night_special: &[&Character],
         other_night_special: &[&Character],
-    ) where
+    ) -> io::Result<()>
+    where
         T: Write,
     {
-        self.write_head(writer);
-        self.write_aside(writer);
-        self.begin_main(writer);
-        self.write_intro_page(writer);
+        self.write_head(writer)?;
+        self.write_aside(writer)?;
+        self.begin_main(writer)?;
+        self.write_intro_page(writer)?;
         for character in &self.characters {
-            self.write_character_page(writer, character);
+            self.write_character_page(writer, character)?;
         }
-        self.write_night_order_page(writer, first_night_special, other_night_special);
-        self.end_main(writer);
-        self.write_end(writer);
+        self.write_jinx_page(writer)?;
+        self.write_night_order_page(writer, first_night_special, other_night_special)?;
+        self.end_main(writer)?;
+        self.write_end(writer)?;
+
+        Ok(())
     }
 
-    fn write_head<T>(&self, writer: &mut T)
+    fn write_head<T>(&self, writer: &mut T) -> io::Result<()>
     where
         T: Write,
     {
         write!(
             writer,
             "<!DOCTYPE html><html lang=\"en\"><head><meta charset=\"UTF-8\"><meta name=\"viewport\" content=\"width=device-width, initial-scale=1.0\"><title>{}</title><style>{STYLE}</style></head><body>",
-            self.name
+            escape(&self.name)
         )
-        .unwrap();
+        ?;
+
+        Ok(())
     }
 
-    fn write_end<T>(&self, writer: &mut T)
+    fn write_end<T>(&self, writer: &mut T) -> io::Result<()>
     where
         T: Write,
     {
-        write!(writer, "</body></html>").unwrap();
+        write!(writer, "</body></html>")?;
+
+        Ok(())
     }
 
-    fn begin_page<T>(&self, writer: &mut T, id: &str, class: Option<&str>)
+    fn begin_page<T>(&self, writer: &mut T, id: &str, class: Option<&str>) -> io::Result<()>
     where
         T: Write,
     {
+        let id = escape(id);
         if let Some(class) = class {
-            write!(writer, "<div id=\"{id}\" class=\"page {class}\">").unwrap();
+            write!(writer, "<div id=\"{id}\" class=\"page {class}\">")?;
         } else {
-            write!(writer, "<div id=\"{id}\" class=\"page\">").unwrap();
+            write!(writer, "<div id=\"{id}\" class=\"page\">")?;
         }
+
+        Ok(())
     }
 
-    fn end_page<T>(&self, writer: &mut T)
+    fn end_page<T>(&self, writer: &mut T) -> io::Result<()>
     where
         T: Write,
     {
-        write!(writer, "</div><div class=\"page-separator\"></div>").unwrap();
+        write!(writer, "</div><div class=\"page-separator\"></div>")?;
+
+        Ok(())
     }
 
-    fn begin_main<T>(&self, writer: &mut T)
+    fn begin_main<T>(&self, writer: &mut T) -> io::Result<()>
     where
         T: Write,
     {
-        write!(writer, "<main>").unwrap();
+        write!(writer, "<main>")?;
+
+        Ok(())
     }
 
-    fn end_main<T>(&self, writer: &mut T)
+    fn end_main<T>(&self, writer: &mut T) -> io::Result<()>
     where
         T: Write,
     {
-        write!(writer, "</main>").unwrap();
+        write!(writer, "</main>")?;
+
+        Ok(())
     }
 
-    fn write_aside<T>(&self, writer: &mut T)
+    fn write_aside<T>(&self, writer: &mut T) -> io::Result<()>
     where
         T: Write,
     {
         write!(
             writer,
             "<aside><a href=\"#intro\" class=\"intro\">Intro</a>"
-        )
-        .unwrap();
+        )?;
         for character in &self.characters {
             write!(
                 writer,
                 "<a href=\"#{}\" class=\"{}\">{}</a>",
-                character.id,
+                escape(&character.id),
                 character.team.to_str(),
-                character.name
-            )
-            .unwrap();
+                escape(&character.name)
+            )?;
+        }
+        if self.has_jinxes_or_rules() {
+            write!(
+                writer,
+                "<a href=\"#jinxes\" class=\"jinxes\">Jinxes &amp; Rules</a>"
+            )?;
         }
         write!(
             writer,
             "<a href=\"#night-order\" class=\"night-order\">Night Order</a></aside>"
-        )
-        .unwrap();
+        )?;
+
+        Ok(())
     }
 
-    fn write_intro_page<T>(&self, writer: &mut T)
+    fn write_intro_page<T>(&self, writer: &mut T) -> io::Result<()>
     where
         T: Write,
     {
-        self.begin_page(writer, "intro", None);
+        self.begin_page(writer, "intro", None)?;
 
         for line in &self.almanac.intro {
-            write!(writer, "<p class=\"intro\">{}</p>", line).unwrap();
+            write!(writer, "<p class=\"intro\">{}</p>", render(line))?;
+        }
+        self.write_details(writer)?;
+        self.write_setup_table(writer)?;
+
+        for (title, lines) in [
+            ("THEMES", &self.almanac.themes),
+            ("DESIGNER NOTES", &self.almanac.notes),
+            ("CHANGELOG", &self.almanac.changelog),
+            ("PLAYTESTING", &self.almanac.playtesting),
+        ] {
+            if !lines.is_empty() {
+                write!(writer, "<h3>{title}</h3>")?;
+                for line in lines {
+                    write!(writer, "<p>{}</p>", render(line))?;
+                }
+            }
+        }
+        self.write_contents(writer)?;
+
+        self.end_page(writer)?;
+
+        Ok(())
+    }
+
+    fn write_details<T>(&self, writer: &mut T) -> io::Result<()>
+    where
+        T: Write,
+    {
+        let mut details = vec![];
+        if let Some(difficulty) = &self.almanac.difficulty {
+            details.push(("Difficulty", render(difficulty)));
+        }
+        if let Some((min, max)) = self.almanac.players {
+            let players = if min == max {
+                min.to_string()
+            } else {
+                format!("{min}&ndash;{max}")
+            };
+            details.push(("Players", players));
+        }
+        if !self.almanac.tags.is_empty() {
+            let tags: Vec<_> = self.almanac.tags.iter().map(|tag| escape(tag)).collect();
+            details.push(("Tags", tags.join(", ")));
+        }
+        if details.is_empty() {
+            return Ok(());
+        }
+
+        write!(writer, "<dl class=\"details\">")?;
+        for (term, value) in details {
+            write!(writer, "<dt>{term}</dt><dd>{value}</dd>")?;
+        }
+        write!(writer, "</dl>")?;
+
+        Ok(())
+    }
+
+    /// Links to every character page, grouped by team
+    fn write_contents<T>(&self, writer: &mut T) -> io::Result<()>
+    where
+        T: Write,
+    {
+        write!(writer, "<h3>CHARACTERS</h3><div class=\"contents\">")?;
+        for team in [
+            Team::Townsfolk,
+            Team::Outsider,
+            Team::Minion,
+            Team::Demon,
+            Team::Traveller,
+            Team::Fabled,
+            Team::Special,
+        ] {
+            let mut characters = self
+                .characters
+                .iter()
+                .filter(|character| character.team == team)
+                .peekable();
+            if characters.peek().is_none() {
+                continue;
+            }
+            write!(
+                writer,
+                "<ul class=\"{}\"><li class=\"team\">{}</li>",
+                team.to_str(),
+                team.heading()
+            )?;
+            for character in characters {
+                write!(
+                    writer,
+                    "<li><a href=\"#{}\">{}</a></li>",
+                    escape(&character.id),
+                    escape(&character.name)
+                )?;
+            }
+            write!(writer, "</ul>")?;
         }
+        write!(writer, "</div>")?;
 
-        self.end_page(writer);
+        Ok(())
     }
 
-    fn write_character_page<T>(&self, writer: &mut T, character: &Character)
+    fn write_setup_table<T>(&self, writer: &mut T) -> io::Result<()>
     where
         T: Write,
     {
-        self.begin_page(writer, &character.id, Some(character.team.to_str()));
+        let player_counts = self.player_counts();
+        let has_modified = player_counts
+            .iter()
+            .any(|player_count| !player_count.modified.is_empty());
+
+        write!(writer, "<table class=\"setup\"><tr><th>Players</th><th class=\"townsfolk\">Townsfolk</th><th class=\"outsider\">Outsiders</th><th class=\"minion\">Minions</th><th class=\"demon\">Demons</th>")?;
+        if has_modified {
+            write!(writer, "<th>With setup</th>")?;
+        }
+        write!(writer, "</tr>")?;
+
+        for player_count in player_counts {
+            let base = player_count.base;
+            write!(
+                writer,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>",
+                player_count.players, base.townsfolk, base.outsiders, base.minions, base.demons
+            )?;
+            if has_modified {
+                let modified: Vec<_> = player_count
+                    .modified
+                    .iter()
+                    .map(|distribution| {
+                        format!(
+                            "{}/{}/{}/{}",
+                            distribution.townsfolk,
+                            distribution.outsiders,
+                            distribution.minions,
+                            distribution.demons
+                        )
+                    })
+                    .collect();
+                write!(writer, "<td>{}</td>", modified.join(", "))?;
+            }
+            write!(writer, "</tr>")?;
+        }
+
+        write!(writer, "</table>")?;
 
-        write!(writer, "<p class=\"team\">{}</p>", character.team.to_str()).unwrap();
+        Ok(())
+    }
+
+    fn write_character_page<T>(&self, writer: &mut T, character: &Character) -> io::Result<()>
+    where
+        T: Write,
+    {
+        self.begin_page(writer, &character.id, Some(character.team.to_str()))?;
+
+        write!(writer, "<p class=\"team\">{}</p>", character.team.to_str())?;
         if let Some(image) = character.image.first() {
-            write!(writer, "<img class=\"char-image\" src=\"{}\" />", image).unwrap();
+            write!(
+                writer,
+                "<img class=\"char-image\" src=\"{}\" />",
+                escape(image)
+            )?;
         }
-        write!(writer, "<h2 class=\"name\">{}</h2>", character.name).unwrap();
-        write!(writer, "<p class=\"ability\">{}</p>", character.ability).unwrap();
-        write!(writer, "<hr />").unwrap();
+        write!(
+            writer,
+            "<h2 class=\"name\">{}</h2>",
+            escape(&character.name)
+        )?;
+        write!(
+            writer,
+            "<p class=\"ability\">{}</p>",
+            render(&character.ability)
+        )?;
+        write!(writer, "<hr />")?;
         if !character.flavour.is_empty() {
-            write!(writer, "<p class=\"flavour\">\"{}\"</p>", character.flavour).unwrap();
+            write!(
+                writer,
+                "<p class=\"flavour\">\"{}\"</p>",
+                render(&character.flavour)
+            )?;
         }
 
         if !character.overview_short.is_empty() {
             write!(
                 writer,
                 "<p class=\"overview-short\">{}</p>",
-                character.overview_short
-            )
-            .unwrap();
+                render(&character.overview_short)
+            )?;
         }
         if !character.overview_long.is_empty() {
-            write!(writer, "<ul>").unwrap();
+            write!(writer, "<ul>")?;
             for line in &character.overview_long {
-                write!(writer, "<li>{}</li>", line).unwrap();
+                write!(writer, "<li>{}</li>", render(line))?;
             }
-            write!(writer, "</ul>").unwrap();
+            write!(writer, "</ul>")?;
         }
         if !character.examples.is_empty() {
-            write!(writer, "<h3>EXAMPLES</h3>").unwrap();
+            write!(writer, "<h3>EXAMPLES</h3>")?;
             for line in &character.examples {
-                write!(writer, "<p>{}</p>", line).unwrap();
+                write!(writer, "<p>{}</p>", render(line))?;
             }
         }
         if !character.how_to_run.is_empty() {
-            write!(writer, "<h3>HOW TO RUN</h3>").unwrap();
+            write!(writer, "<h3>HOW TO RUN</h3>")?;
             for line in &character.how_to_run {
-                write!(writer, "<p>{}</p>", line).unwrap();
+                write!(writer, "<p>{}</p>", render(line))?;
             }
         }
         if !character.advice.is_empty() {
             for line in &character.advice {
-                write!(writer, "<p class=\"advice\">{}</p>", line).unwrap();
+                write!(writer, "<p class=\"advice\">{}</p>", render(line))?;
+            }
+        }
+        let jinxes: Vec<_> = self
+            .jinx_pairs()
+            .into_iter()
+            .filter(|jinx| jinx.first.id == character.id || jinx.second.id == character.id)
+            .collect();
+        let rules: Vec<_> = self
+            .character_rules()
+            .into_iter()
+            .filter(|rule| rule.character.id == character.id)
+            .collect();
+        if !jinxes.is_empty() || !rules.is_empty() {
+            write!(writer, "<h3>JINXES &amp; RULES</h3>")?;
+            for jinx in jinxes {
+                let other = if jinx.first.id == character.id {
+                    jinx.second
+                } else {
+                    jinx.first
+                };
+                write!(
+                    writer,
+                    "<p class=\"jinx-link\"><a href=\"#{}\">Jinxed with {}</a></p>",
+                    escape(&jinx.anchor()),
+                    escape(&other.name)
+                )?;
+            }
+            for rule in rules {
+                write!(
+                    writer,
+                    "<p class=\"jinx-link\"><a href=\"#{}\">{}</a></p>",
+                    escape(&rule.anchor),
+                    render(rule.reason)
+                )?;
             }
         }
         if !character.attribution.is_empty() {
-            write!(writer, "<h3>ATTRIBUTION</h3>").unwrap();
+            write!(writer, "<h3>ATTRIBUTION</h3>")?;
             for line in &character.attribution {
-                write!(writer, "<p>{}</p>", line).unwrap();
+                write!(writer, "<p>{}</p>", render(line))?;
+            }
+        }
+
+        self.end_page(writer)?;
+
+        Ok(())
+    }
+
+    fn write_jinx_page<T>(&self, writer: &mut T) -> io::Result<()>
+    where
+        T: Write,
+    {
+        if !self.has_jinxes_or_rules() {
+            return Ok(());
+        }
+
+        self.begin_page(writer, "jinxes", None)?;
+
+        write!(writer, "<h2 class=\"jinxes\">JINXES &amp; RULES</h2>")?;
+
+        if !self.bootlegger_rules.is_empty() {
+            write!(writer, "<h3>SCRIPT RULES</h3>")?;
+            for rule in &self.bootlegger_rules {
+                write!(writer, "<p class=\"rule\">{}</p>", render(rule))?;
+            }
+        }
+
+        let rules = self.character_rules();
+        if !rules.is_empty() {
+            write!(writer, "<h3>CHARACTER RULES</h3>")?;
+            for rule in rules {
+                write!(
+                    writer,
+                    "<div class=\"jinx\" id=\"{}\">",
+                    escape(&rule.anchor)
+                )?;
+                write_icon(writer, rule.character)?;
+                write!(writer, "<div></div>")?;
+                write!(
+                    writer,
+                    "<p><a href=\"#{}\">{}</a>: {}</p></div>",
+                    escape(&rule.character.id),
+                    escape(&rule.character.name),
+                    render(rule.reason)
+                )?;
+            }
+        }
+
+        let jinxes = self.jinx_pairs();
+        if !jinxes.is_empty() {
+            write!(writer, "<h3>JINXES</h3>")?;
+            for jinx in jinxes {
+                for (idx, reason) in jinx.reasons().into_iter().enumerate() {
+                    if idx == 0 {
+                        write!(
+                            writer,
+                            "<div class=\"jinx\" id=\"{}\">",
+                            escape(&jinx.anchor())
+                        )?;
+                    } else {
+                        write!(writer, "<div class=\"jinx\">")?;
+                    }
+                    write_icon(writer, jinx.first)?;
+                    write_icon(writer, jinx.second)?;
+                    write!(
+                        writer,
+                        "<p><a href=\"#{}\">{}</a> &amp; <a href=\"#{}\">{}</a>: {}</p></div>",
+                        escape(&jinx.first.id),
+                        escape(&jinx.first.name),
+                        escape(&jinx.second.id),
+                        escape(&jinx.second.name),
+                        render(reason)
+                    )?;
+                }
             }
         }
 
-        self.end_page(writer);
+        self.end_page(writer)?;
+
+        Ok(())
+    }
+
+    pub fn has_jinxes_or_rules(&self) -> bool {
+        !self.bootlegger_rules.is_empty()
+            || !self.character_rules().is_empty()
+            || !self.jinx_pairs().is_empty()
+    }
+
+    /// Pairs of different characters on the script with a jinx on at least one of them, in the
+    /// order the jinxes are listed
+    pub fn jinx_pairs(&self) -> Vec<JinxPair<'_>> {
+        let mut pairs: Vec<JinxPair> = vec![];
+
+        for character in &self.characters {
+            for jinx in &character.jinxes {
+                if jinx.id == character.id {
+                    continue;
+                }
+                let Some(other) = self.characters.iter().find(|other| other.id == jinx.id) else {
+                    continue;
+                };
+                if let Some(pair) = pairs
+                    .iter_mut()
+                    .find(|pair| pair.first.id == other.id && pair.second.id == character.id)
+                {
+                    pair.second_reason.get_or_insert(&jinx.reason);
+                    continue;
+                }
+                if pairs
+                    .iter()
+                    .any(|pair| pair.first.id == character.id && pair.second.id == other.id)
+                {
+                    continue;
+                }
+
+                pairs.push(JinxPair {
+                    first: character,
+                    second: other,
+                    first_reason: Some(&jinx.reason),
+                    second_reason: None,
+                });
+            }
+        }
+
+        pairs
+    }
+
+    /// Jinxes a character has with itself, which is how bootlegger patches store added rules
+    pub fn character_rules(&self) -> Vec<CharacterRule<'_>> {
+        let mut rules = vec![];
+
+        for character in &self.characters {
+            for (idx, jinx) in character
+                .jinxes
+                .iter()
+                .filter(|jinx| jinx.id == character.id)
+                .enumerate()
+            {
+                rules.push(CharacterRule {
+                    character,
+                    reason: &jinx.reason,
+                    anchor: format!("rule-{}-{idx}", character.id),
+                });
+            }
+        }
+
+        rules
     }
 
     fn write_night_order_page<T>(
@@ -188,74 +577,123 @@ impl Script {
         writer: &mut T,
         first_night_special: &[&Character],
         other_night_special: &[&Character],
-    ) where
+    ) -> io::Result<()>
+    where
         T: Write,
     {
-        self.begin_page(writer, "night-order", None);
+        self.begin_page(writer, "night-order", None)?;
 
         write!(
             writer,
             "<h2 class=\"night-order\">NIGHT ORDER</h2><div class=\"night-order-container\">"
-        )
-        .unwrap();
-
-        let mut sorted: Vec<_> = self
-            .characters
-            .iter()
-            .filter(|character| character.first_night > 0.0)
-            .collect();
-        sorted.extend(first_night_special);
-        sorted.sort_unstable_by(|a, b| {
-            a.first_night
-                .partial_cmp(&b.first_night)
-                .unwrap_or(Ordering::Equal)
-        });
+        )?;
 
-        self.write_night_order(writer, "FIRST NIGHT", &sorted);
+        let sorted = self.first_night_order(first_night_special);
+        self.write_night_order(writer, "FIRST NIGHT", &sorted, |character| {
+            &character.first_night_reminder
+        })?;
 
-        let mut sorted: Vec<_> = self
-            .characters
-            .iter()
-            .filter(|character| character.other_night > 0.0)
-            .collect();
-        sorted.extend(other_night_special);
-        sorted.sort_unstable_by(|a, b| {
-            a.other_night
-                .partial_cmp(&b.other_night)
-                .unwrap_or(Ordering::Equal)
-        });
+        let sorted = self.other_night_order(other_night_special);
+        self.write_night_order(writer, "OTHER NIGHTS", &sorted, |character| {
+            &character.other_night_reminder
+        })?;
 
-        self.write_night_order(writer, "OTHER NIGHTS", &sorted);
+        self.end_page(writer)?;
 
-        self.end_page(writer);
+        Ok(())
     }
 
-    fn write_night_order<T>(&self, writer: &mut T, header: &str, characters: &[&Character])
+    fn write_night_order<T, F>(
+        &self,
+        writer: &mut T,
+        header: &str,
+        characters: &[&Character],
+        reminder: F,
+    ) -> io::Result<()>
     where
         T: Write,
+        F: Fn(&Character) -> &str,
     {
         write!(
             writer,
             "<div class=\"night-order-list\"><h3 class=\"night-order-type\">{header}</h3><div class=\"night-order-list-container\">"
         )
-        .unwrap();
+        ?;
 
         for character in characters {
-            write!(writer, "<div class=\"night-order-entry\">").unwrap();
+            write!(writer, "<div class=\"night-order-entry\">")?;
             if let Some(image) = character.image.first() {
-                write!(writer, "<img src=\"{}\" />", image).unwrap()
+                write!(writer, "<img src=\"{}\" />", escape(image))?;
             } else {
-                write!(writer, "<div></div>").unwrap();
+                write!(writer, "<div></div>")?;
             }
-            write!(writer, "<p>{}</p></div>", character.name).unwrap();
+            write!(
+                writer,
+                "<div class=\"night-order-text\"><p>{}</p><p class=\"night-order-reminder\">{}</p></div></div>",
+                escape(&character.name),
+                render_reminder(reminder(character))
+            )
+            ?;
         }
 
-        write!(writer, "</div></div>").unwrap();
+        write!(writer, "</div></div>")?;
+
+        Ok(())
+    }
+}
+
+/// Two characters on a script with a jinx on at least one of them
+pub struct JinxPair<'a> {
+    pub first: &'a Character,
+    pub second: &'a Character,
+    /// The jinx stored on `first`
+    pub first_reason: Option<&'a str>,
+    /// The jinx stored on `second`
+    pub second_reason: Option<&'a str>,
+}
+
+impl<'a> JinxPair<'a> {
+    pub fn anchor(&self) -> String {
+        format!("jinx-{}-{}", self.first.id, self.second.id)
+    }
+
+    pub fn is_one_sided(&self) -> bool {
+        self.first_reason.is_none() || self.second_reason.is_none()
+    }
+
+    /// The jinx text to show, both sides when they differ
+    pub fn reasons(&self) -> Vec<&'a str> {
+        let mut reasons: Vec<&str> = self.first_reason.into_iter().collect();
+        if let Some(second) = self.second_reason {
+            if !reasons.contains(&second) {
+                reasons.push(second);
+            }
+        }
+        reasons
     }
 }
 
+pub struct CharacterRule<'a> {
+    pub character: &'a Character,
+    pub reason: &'a str,
+    pub anchor: String,
+}
+
+pub fn write_icon<T>(writer: &mut T, character: &Character) -> io::Result<()>
+where
+    T: Write,
+{
+    if let Some(image) = character.image.first() {
+        write!(writer, "<img src=\"{}\" />", escape(image))?;
+    } else {
+        write!(writer, "<div></div>")?;
+    }
+
+    Ok(())
+}
+
 impl Team {
-    fn to_str(&self) -> &str {
+    pub fn to_str(&self) -> &str {
         match self {
             Team::Townsfolk => "townsfolk",
             Team::Outsider => "outsider",
@@ -266,9 +704,30 @@ impl Team {
             Team::Special => "special",
         }
     }
+
+    /// The name of the team as a heading for a group of its characters
+    pub fn heading(&self) -> &str {
+        match self {
+            Team::Townsfolk => "Townsfolk",
+            Team::Outsider => "Outsiders",
+            Team::Minion => "Minions",
+            Team::Demon => "Demons",
+            Team::Traveller => "Travellers",
+            Team::Fabled => "Fabled",
+            Team::Special => "Special",
+        }
+    }
 }
 
-pub fn write_index<T>(writer: &mut T, entries: &[(String, String)])
+/// A built script as listed on the index page
+pub struct IndexEntry {
+    pub file_name: String,
+    pub name: String,
+    /// The source file the script is a variant of
+    pub base: Option<String>,
+}
+
+pub fn write_index<T>(writer: &mut T, entries: &[IndexEntry], config: &Config) -> io::Result<()>
 where
     T: Write,
 {
@@ -276,11 +735,33 @@ where
         writer,
         "<!DOCTYPE html><html lang=\"en\"><head><meta charset=\"UTF-8\"><meta name=\"viewport\" content=\"width=device-width, initial-scale=1.0\"><title>Toto's Script Index</title><style>{INDEX_STYLE}</style></head><body>",
     )
-    .unwrap();
-
-    for (id, name) in entries {
-        write!(writer, "<div class=\"entry\"><a class=\"almanac\" href=\"/botc-scripts/{id}.html\">{name}</a><a class=\"json\" href=\"/botc-scripts/{id}.official.json\">Json</a></div>").unwrap();
+    ?;
+
+    let prefix = escape(&config.urls.index);
+    for entry in entries {
+        let id = &entry.file_name;
+        let almanac = escape(&config.output.almanac(id));
+        let json = escape(&config.output.json(id));
+        let a4 = escape(&config.output.sheet(id, Paper::A4));
+        let letter = escape(&config.output.sheet(id, Paper::Letter));
+        let name = escape(&entry.name);
+        write!(writer, "<div class=\"entry\"><a class=\"almanac\" href=\"{prefix}{almanac}\">{name}</a><a class=\"json\" href=\"{prefix}{json}\">Json</a><a class=\"sheet\" href=\"{prefix}{a4}\">A4</a><a class=\"sheet\" href=\"{prefix}{letter}\">Letter</a>")?;
+        if let Some(base) = &entry.base {
+            let base_name = entries
+                .iter()
+                .find(|other| other.file_name == *base)
+                .map_or(base, |other| &other.name);
+            write!(
+                writer,
+                "<a class=\"base\" href=\"{prefix}{}\">Variant of {}</a>",
+                escape(&config.output.almanac(base)),
+                escape(base_name)
+            )?;
+        }
+        write!(writer, "</div>")?;
     }
 
-    write!(writer, "</body>").unwrap();
+    write!(writer, "</body>")?;
+
+    Ok(())
 }
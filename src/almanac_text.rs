@@ -0,0 +1,248 @@
+use std::io::{self, Write};
+
+use crate::{
+    character::Character,
+    markup::{render_as, Markup},
+    script::Script,
+};
+
+impl Script {
+    pub fn write_almanac_text<T>(&self, writer: &mut T, markup: Markup) -> io::Result<()>
+    where
+        T: Write,
+    {
+        let mut out = vec![
+            heading(1, &self.name, markup),
+            render_as(&format!("by {}", self.author), markup),
+        ];
+        out.extend(
+            self.almanac
+                .intro
+                .iter()
+                .map(|line| render_as(line, markup)),
+        );
+
+        let almanac = &self.almanac;
+        let mut details = vec![];
+        if let Some(difficulty) = &almanac.difficulty {
+            details.push(format!("*Difficulty:* {difficulty}"));
+        }
+        if let Some((min, max)) = almanac.players {
+            if min == max {
+                details.push(format!("*Players:* {min}"));
+            } else {
+                details.push(format!("*Players:* {min}-{max}"));
+            }
+        }
+        if !almanac.tags.is_empty() {
+            details.push(format!("*Tags:* {}", almanac.tags.join(", ")));
+        }
+        if !details.is_empty() {
+            out.push(list(&details, markup));
+        }
+        for (title, lines) in [
+            ("Themes", &almanac.themes),
+            ("Designer Notes", &almanac.notes),
+            ("Changelog", &almanac.changelog),
+            ("Playtesting", &almanac.playtesting),
+        ] {
+            if !lines.is_empty() {
+                out.push(heading(2, title, markup));
+                out.extend(lines.iter().map(|line| render_as(line, markup)));
+            }
+        }
+
+        for character in &self.characters {
+            out.extend(self.character_text(character, 2, markup));
+        }
+
+        if !self.bootlegger_rules.is_empty() {
+            out.push(heading(2, "Script Rules", markup));
+            out.push(list(&self.bootlegger_rules, markup));
+        }
+
+        for (title, order) in [
+            ("First Night", self.first_night_order(&[])),
+            ("Other Nights", self.other_night_order(&[])),
+        ] {
+            if order.is_empty() {
+                continue;
+            }
+            out.push(heading(2, title, markup));
+            let names: Vec<String> = order
+                .iter()
+                .enumerate()
+                .map(|(idx, character)| {
+                    format!("{}. {}", idx + 1, render_as(&character.name, markup))
+                })
+                .collect();
+            out.push(names.join("\n"));
+        }
+
+        writeln!(writer, "{}", out.join("\n\n"))?;
+
+        Ok(())
+    }
+
+    pub fn write_character_text<T>(
+        &self,
+        writer: &mut T,
+        character: &Character,
+        markup: Markup,
+    ) -> io::Result<()>
+    where
+        T: Write,
+    {
+        writeln!(
+            writer,
+            "{}",
+            self.character_text(character, 1, markup).join("\n\n")
+        )?;
+
+        Ok(())
+    }
+
+    /// The paragraphs of a character's page, its name as a heading of `level`
+    fn character_text(&self, character: &Character, level: usize, markup: Markup) -> Vec<String> {
+        let mut out = vec![
+            heading(level, &character.name, markup),
+            italic(&render_as(character.team.to_str(), markup), markup),
+            quote(&render_as(&character.ability, markup), markup),
+        ];
+        if !character.flavour.is_empty() {
+            out.push(italic(&render_as(&character.flavour, markup), markup));
+        }
+        if !character.overview_short.is_empty() {
+            out.push(render_as(&character.overview_short, markup));
+        }
+        if !character.overview_long.is_empty() {
+            out.push(list(&character.overview_long, markup));
+        }
+
+        for (title, lines) in [
+            ("Examples", &character.examples),
+            ("How to Run", &character.how_to_run),
+            ("Advice", &character.advice),
+        ] {
+            if !lines.is_empty() {
+                out.push(heading(level + 1, title, markup));
+                out.extend(lines.iter().map(|line| render_as(line, markup)));
+            }
+        }
+
+        let mut jinxes: Vec<String> = self
+            .jinx_pairs()
+            .into_iter()
+            .filter(|jinx| jinx.first.id == character.id || jinx.second.id == character.id)
+            .flat_map(|jinx| {
+                let other = if jinx.first.id == character.id {
+                    jinx.second
+                } else {
+                    jinx.first
+                };
+                jinx.reasons()
+                    .into_iter()
+                    .map(move |reason| format!("Jinxed with {}: {reason}", other.name))
+            })
+            .collect();
+        jinxes.extend(
+            self.character_rules()
+                .into_iter()
+                .filter(|rule| rule.character.id == character.id)
+                .map(|rule| rule.reason.to_owned()),
+        );
+        if !jinxes.is_empty() {
+            out.push(heading(level + 1, "Jinxes & Rules", markup));
+            out.push(list(&jinxes, markup));
+        }
+
+        if !character.attribution.is_empty() {
+            out.push(heading(level + 1, "Attribution", markup));
+            out.extend(
+                character
+                    .attribution
+                    .iter()
+                    .map(|line| render_as(line, markup)),
+            );
+        }
+
+        out
+    }
+}
+
+fn heading(level: usize, text: &str, markup: Markup) -> String {
+    let text = render_as(text, markup);
+    match (markup, level) {
+        (Markup::Plain, 1) => format!("{text}\n{}", "=".repeat(text.chars().count())),
+        (Markup::Plain, 2) => format!("{text}\n{}", "-".repeat(text.chars().count())),
+        (Markup::Plain, _) => text.to_uppercase(),
+        _ => format!("{} {text}", "#".repeat(level)),
+    }
+}
+
+/// Wraps rendered text in `*`, which cannot clash with an `_` inside it
+fn italic(text: &str, markup: Markup) -> String {
+    match markup {
+        Markup::Plain => text.to_owned(),
+        _ => format!("*{text}*"),
+    }
+}
+
+fn quote(text: &str, markup: Markup) -> String {
+    match markup {
+        Markup::Plain => text.to_owned(),
+        _ => format!("> {text}"),
+    }
+}
+
+fn list(lines: &[String], markup: Markup) -> String {
+    lines
+        .iter()
+        .map(|line| format!("- {}", render_as(line, markup)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        character::tests::{character, character_list},
+        diagnostics::Diagnostics,
+        markup::Markup,
+        script::Script,
+    };
+
+    fn text(flavour: &str, markup: Markup) -> String {
+        let mut monk = character("monk", "Monk", "townsfolk", "Each night*");
+        monk.flavour = flavour.to_owned();
+        let characters = character_list(vec![monk]);
+        let script = Script::parse(
+            "test",
+            "Test\nTester\n\nbootlegger No peeking.\nmonk",
+            &characters,
+            &mut Diagnostics::default(),
+        )
+        .unwrap();
+        let mut out = vec![];
+        script.write_almanac_text(&mut out, markup).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn flavour_keeps_its_own_markup() {
+        let out = text("The snake_case *cult* of _Mammon_", Markup::Markdown);
+        assert!(
+            out.contains("\n*The snake\\_case **cult** of _Mammon_*\n"),
+            "{out}"
+        );
+
+        let out = text("The snake_case *cult* of _Mammon_", Markup::Plain);
+        assert!(out.contains("\nThe snake_case cult of Mammon\n"), "{out}");
+    }
+
+    #[test]
+    fn script_rules_heading_matches_the_html_almanac() {
+        let out = text("", Markup::Markdown);
+        assert!(out.contains("## Script Rules\n\n- No peeking."), "{out}");
+    }
+}
@@ -0,0 +1,487 @@
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    character::{Character, Team},
+    script::Script,
+    setup::{Distribution, SetupModifier, BASE_DISTRIBUTIONS},
+};
+
+const DRAW_ATTEMPTS: usize = 100;
+
+const DRAW_TEAMS: [Team; 4] = [Team::Demon, Team::Minion, Team::Outsider, Team::Townsfolk];
+
+pub struct Bag<'a> {
+    pub players: usize,
+    pub distribution: Distribution,
+    /// Every character in play, duplicates included
+    pub in_play: Vec<&'a Character>,
+    /// The tokens handed out, differing from `in_play` for bag-disabled characters
+    pub tokens: Vec<&'a Character>,
+    pub travellers: Vec<&'a Character>,
+    /// Three good characters not in play for the Demon
+    pub bluffs: Vec<&'a Character>,
+}
+
+struct Drawn<'a> {
+    character: &'a Character,
+    /// Added by another character's setup modifier, so it cannot be swapped out
+    forced: bool,
+    applied: bool,
+}
+
+impl Script {
+    /// Draws the Demon and Minions first, then the Outsiders and Townsfolk, applying setup
+    /// modifiers as they are drawn
+    pub fn draw_bag<R>(
+        &self,
+        players: usize,
+        travellers: usize,
+        rng: &mut R,
+    ) -> Result<Bag<'_>, String>
+    where
+        R: Rng,
+    {
+        let Some((_, base)) = BASE_DISTRIBUTIONS
+            .iter()
+            .find(|(count, _)| *count == players)
+        else {
+            return Err(format!("Bags need 5 to 15 players, not {players}"));
+        };
+
+        // Some combinations cannot work out, like a Vigormortis without Outsiders to remove,
+        // so the storyteller starts over just like they would at the table
+        let mut attempt = self.draw_characters(*base, rng);
+        for _ in 1..DRAW_ATTEMPTS {
+            if attempt.is_ok() {
+                break;
+            }
+            attempt = self.draw_characters(*base, rng);
+        }
+        let (distribution, drawn) = attempt?;
+
+        let mut in_play: Vec<&Character> = drawn.iter().map(|drawn| drawn.character).collect();
+        in_play.sort_by(|a, b| a.team.cmp(&b.team).then_with(|| a.name.cmp(&b.name)));
+
+        let mut tokens = vec![];
+        for character in &in_play {
+            if !character.has_selection("bag-disabled") {
+                tokens.push(*character);
+            } else if character.team != Team::Demon {
+                let replacement = self
+                    .pool(&Team::Townsfolk, &in_play)
+                    .into_iter()
+                    .filter(|candidate| !tokens.iter().any(|token| token.id == candidate.id))
+                    .collect::<Vec<_>>()
+                    .choose(rng)
+                    .copied()
+                    .ok_or_else(|| {
+                        format!("No Townsfolk left to stand in for the {}", character.name)
+                    })?;
+                tokens.push(replacement);
+            }
+        }
+
+        let mut drawn_travellers: Vec<&Character> = self
+            .pool(&Team::Traveller, &in_play)
+            .choose_multiple(rng, travellers)
+            .copied()
+            .collect();
+        if drawn_travellers.len() < travellers {
+            return Err(format!(
+                "The script has {} travellers, not {travellers}",
+                drawn_travellers.len()
+            ));
+        }
+        let mut extra_travellers = vec![];
+        for traveller in &drawn_travellers {
+            if let Some(modifier) = self.modifier(&traveller.id) {
+                for change in &modifier.changes {
+                    if change.character.as_ref() == Some(&traveller.id) {
+                        let option = *change.options.choose(rng).unwrap_or(&0);
+                        for _ in 0..option.max(0) {
+                            extra_travellers.push(*traveller);
+                        }
+                    }
+                }
+            }
+        }
+        drawn_travellers.extend(extra_travellers);
+
+        let bluffs = [Team::Townsfolk, Team::Outsider]
+            .iter()
+            .flat_map(|team| self.pool(team, &in_play))
+            .filter(|candidate| {
+                !candidate.has_selection("bag-disabled")
+                    && !tokens.iter().any(|token| token.id == candidate.id)
+            })
+            .collect::<Vec<_>>()
+            .choose_multiple(rng, 3)
+            .copied()
+            .collect();
+
+        Ok(Bag {
+            players,
+            distribution,
+            in_play,
+            tokens,
+            travellers: drawn_travellers,
+            bluffs,
+        })
+    }
+
+    fn draw_characters<'a, R>(
+        &'a self,
+        base: Distribution,
+        rng: &mut R,
+    ) -> Result<(Distribution, Vec<Drawn<'a>>), String>
+    where
+        R: Rng,
+    {
+        let mut distribution = base;
+        let mut drawn: Vec<Drawn> = vec![];
+        loop {
+            for team in &DRAW_TEAMS {
+                self.fill_team(team, &distribution, &mut drawn, rng)?;
+            }
+
+            let Some(idx) = drawn.iter().position(|drawn| !drawn.applied) else {
+                return Ok((distribution, drawn));
+            };
+            let character = drawn[idx].character;
+            // Another copy of a bag-duplicate character has already changed the setup
+            let copy = drawn
+                .iter()
+                .any(|drawn| drawn.applied && drawn.character.id == character.id);
+            drawn[idx].applied = true;
+            if let Some(modifier) = self.modifier(&character.id).filter(|_| !copy) {
+                self.apply_modifier(modifier, &mut distribution, &mut drawn, rng)?;
+            }
+        }
+    }
+
+    /// Draws or returns characters until the team matches the distribution
+    fn fill_team<'a, R>(
+        &'a self,
+        team: &Team,
+        distribution: &Distribution,
+        drawn: &mut Vec<Drawn<'a>>,
+        rng: &mut R,
+    ) -> Result<(), String>
+    where
+        R: Rng,
+    {
+        let wanted = distribution.count(team).max(0) as usize;
+        // Until its modifier is applied, a character that is not dealt still holds its slot
+        let fills = |drawn: &Drawn| {
+            drawn.character.team == *team && (!drawn.applied || self.is_dealt(drawn.character))
+        };
+        let on_team = |drawn: &Vec<Drawn>| drawn.iter().filter(|drawn| fills(drawn)).count();
+
+        while on_team(drawn) > wanted {
+            let removable: Vec<usize> = drawn
+                .iter()
+                .enumerate()
+                .filter(|(_, drawn)| {
+                    fills(drawn)
+                        && !drawn.forced
+                        && !(drawn.applied && self.modifier(&drawn.character.id).is_some())
+                })
+                .map(|(idx, _)| idx)
+                .collect();
+            let Some(idx) = removable.choose(rng) else {
+                return Err(format!("Too many {} for the setup", team.to_str()));
+            };
+            drawn.remove(*idx);
+        }
+
+        while on_team(drawn) < wanted {
+            let in_play: Vec<&Character> = drawn.iter().map(|drawn| drawn.character).collect();
+            let Some(character) = self.pool(team, &in_play).choose(rng).copied() else {
+                return Err(format!("Not enough {} on the script", team.to_str()));
+            };
+            drawn.push(Drawn {
+                character,
+                forced: false,
+                applied: false,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn apply_modifier<'a, R>(
+        &'a self,
+        modifier: &SetupModifier,
+        distribution: &mut Distribution,
+        drawn: &mut Vec<Drawn<'a>>,
+        rng: &mut R,
+    ) -> Result<(), String>
+    where
+        R: Rng,
+    {
+        for change in &modifier.changes {
+            let options: Vec<i32> = change
+                .options
+                .iter()
+                .copied()
+                .filter(|option| {
+                    let mut next = *distribution;
+                    let delta = change.delta(*option, distribution);
+                    next.apply(&change.team, delta, modifier.absorbs(change));
+                    next.is_valid()
+                })
+                .collect();
+            let Some(option) = options.choose(rng).copied() else {
+                return Err(format!("No room for the setup of {}", modifier.character));
+            };
+            let delta = change.delta(option, distribution);
+            distribution.apply(&change.team, delta, modifier.absorbs(change));
+
+            let Some(id) = &change.character else {
+                continue;
+            };
+            let Some(character) = self
+                .characters
+                .iter()
+                .find(|character| character.id == *id || character.id == format!("patched_{id}"))
+            else {
+                return Err(format!(
+                    "The setup of {} adds {id}, which is not on the script",
+                    modifier.character
+                ));
+            };
+            // Adding yourself means duplicates, anyone else only needs to be in play once
+            let copies = if character.id == modifier.character
+                && character.has_selection("bag-duplicate")
+            {
+                delta
+            } else if delta > 0 && !drawn.iter().any(|drawn| drawn.character.id == character.id) {
+                1
+            } else {
+                0
+            };
+            for _ in 0..copies {
+                drawn.push(Drawn {
+                    character,
+                    forced: true,
+                    applied: true,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Characters whose setup modifier fills their own slot, like the Lil' Monsta, are not
+    /// dealt to anyone
+    fn is_dealt(&self, character: &Character) -> bool {
+        self.modifier(&character.id).is_none_or(|modifier| {
+            !modifier.bag_disabled
+                || !modifier
+                    .changes
+                    .iter()
+                    .any(|change| modifier.absorbs(change) == &modifier.team)
+        })
+    }
+
+    fn modifier(&self, id: &str) -> Option<&SetupModifier> {
+        self.setup_modifiers
+            .iter()
+            .find(|modifier| modifier.character == id)
+    }
+
+    /// Characters of a team that can still be drawn, bag-duplicate ones even when in play
+    fn pool(&self, team: &Team, in_play: &[&Character]) -> Vec<&Character> {
+        self.characters
+            .iter()
+            .filter(|character| {
+                character.team == *team
+                    && (character.has_selection("bag-duplicate")
+                        || !in_play.iter().any(|other| other.id == character.id))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rand::{rngs::StdRng, SeedableRng};
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        character::tests::{character, character_list},
+        diagnostics::Diagnostics,
+    };
+
+    const SEEDS: u64 = 50;
+
+    fn selection(mut character: Character, name: &str) -> Character {
+        character.special = Some(json!([{ "type": "selection", "name": name }]));
+        character
+    }
+
+    fn characters() -> HashMap<String, Character> {
+        let mut characters = vec![
+            selection(
+                character("atheist", "Atheist", "townsfolk", "[No evil characters]"),
+                "bag-duplicate",
+            ),
+            selection(
+                character(
+                    "villageidiot",
+                    "Village Idiot",
+                    "townsfolk",
+                    "[+0 to +2 Village Idiots]",
+                ),
+                "bag-duplicate",
+            ),
+            character("baron", "Baron", "minion", "[+2 Outsiders]"),
+            selection(
+                character("legion", "Legion", "demon", "[Most players are Legion]"),
+                "bag-duplicate",
+            ),
+            selection(
+                character("lilmonsta", "Lil' Monsta", "demon", "[+1 Minion]"),
+                "bag-disabled",
+            ),
+            character("imp", "Imp", "demon", ""),
+        ];
+        for idx in 0..12 {
+            characters.push(character(&format!("town{idx}"), "", "townsfolk", ""));
+        }
+        for idx in 0..4 {
+            characters.push(character(&format!("out{idx}"), "", "outsider", ""));
+            characters.push(character(&format!("minion{idx}"), "", "minion", ""));
+            characters.push(character(&format!("traveller{idx}"), "", "traveller", ""));
+        }
+        character_list(characters)
+    }
+
+    /// A script with the plain characters and `extra`
+    fn script(extra: &[&str]) -> Script {
+        let characters = characters();
+        let mut ids: Vec<String> = characters
+            .keys()
+            .filter(|id| id.ends_with(char::is_numeric))
+            .cloned()
+            .collect();
+        ids.sort();
+        ids.extend(extra.iter().map(|id| id.to_string()));
+        let text = format!("Test\nTester\n\n{}", ids.join("\n"));
+        let mut script =
+            Script::parse("test", &text, &characters, &mut Diagnostics::default()).unwrap();
+        script.resolve_setup(&characters);
+        script
+    }
+
+    fn count(characters: &[&Character], id: &str) -> usize {
+        characters
+            .iter()
+            .filter(|character| character.id == id)
+            .count()
+    }
+
+    fn draws<'a>(script: &'a Script, players: usize) -> impl Iterator<Item = Bag<'a>> {
+        (0..SEEDS).map(move |seed| {
+            script
+                .draw_bag(players, 0, &mut StdRng::seed_from_u64(seed))
+                .unwrap()
+        })
+    }
+
+    #[test]
+    fn follows_the_base_distribution() {
+        let script = script(&["imp"]);
+        for (players, base) in BASE_DISTRIBUTIONS {
+            let bag = script
+                .draw_bag(players, 2, &mut StdRng::seed_from_u64(players as u64))
+                .unwrap();
+            assert_eq!(bag.distribution, base);
+            assert_eq!(bag.in_play.len(), players);
+            assert_eq!(bag.tokens.len(), players);
+            assert_eq!(bag.travellers.len(), 2);
+            assert_eq!(bag.bluffs.len(), 3);
+            for character in &bag.in_play {
+                assert_eq!(count(&bag.in_play, &character.id), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_unsupported_player_counts() {
+        let script = script(&["imp"]);
+        let mut rng = StdRng::seed_from_u64(0);
+        assert!(script.draw_bag(4, 0, &mut rng).is_err());
+        assert!(script.draw_bag(16, 0, &mut rng).is_err());
+        assert!(script.draw_bag(8, 5, &mut rng).is_err());
+    }
+
+    #[test]
+    fn applies_modifiers_in_play() {
+        let script = script(&["imp", "baron"]);
+        for bag in draws(&script, 8) {
+            let outsiders = bag
+                .in_play
+                .iter()
+                .filter(|character| character.team == Team::Outsider)
+                .count();
+            let baron = count(&bag.in_play, "baron") == 1;
+            assert_eq!(outsiders, if baron { 3 } else { 1 });
+        }
+    }
+
+    #[test]
+    fn atheist_games_have_no_evil_characters() {
+        let script = script(&["imp", "baron", "atheist"]);
+        let mut seen = false;
+        for bag in draws(&script, 8) {
+            if count(&bag.in_play, "atheist") > 0 {
+                seen = true;
+                assert!(bag
+                    .in_play
+                    .iter()
+                    .all(|character| matches!(character.team, Team::Townsfolk | Team::Outsider)));
+            } else {
+                assert_eq!(bag.distribution.demons, 1);
+            }
+        }
+        assert!(seen);
+    }
+
+    #[test]
+    fn legion_is_most_players() {
+        let script = script(&["legion"]);
+        for bag in draws(&script, 9) {
+            assert_eq!(count(&bag.in_play, "legion"), 5);
+            assert_eq!(bag.distribution.demons, 5);
+        }
+    }
+
+    #[test]
+    fn bag_duplicates_add_copies_of_themselves() {
+        let script = script(&["imp", "villageidiot"]);
+        let mut copies = vec![];
+        for bag in draws(&script, 10) {
+            let idiots = count(&bag.in_play, "villageidiot");
+            if idiots > 0 {
+                copies.push(idiots);
+            }
+            assert_eq!(bag.in_play.len(), 10);
+        }
+        assert!(copies.iter().any(|copies| *copies > 1));
+    }
+
+    #[test]
+    fn bag_disabled_characters_are_not_dealt() {
+        let script = script(&["lilmonsta"]);
+        for bag in draws(&script, 7) {
+            assert_eq!(bag.distribution, Distribution::new(5, 0, 2, 0));
+            assert_eq!(count(&bag.in_play, "lilmonsta"), 1);
+            assert_eq!(count(&bag.tokens, "lilmonsta"), 0);
+            assert_eq!(bag.tokens.len(), 7);
+        }
+    }
+}
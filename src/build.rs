@@ -0,0 +1,243 @@
+use std::{
+    fs::{create_dir_all, File},
+    io,
+    path::Path,
+};
+
+use crate::{
+    almanac::{write_index, IndexEntry},
+    config::Config,
+    database::CharacterDatabase,
+    diagnostics::Diagnostics,
+    lint::Lint,
+    markup::Markup,
+    script::{file_name, Script, ScriptCache},
+    sheet::Paper,
+    special_characters::{special_characters, SpecialCharacters},
+    validate::{read_schema, validate},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    Json,
+    Almanac,
+    Sheet,
+    NightSheet,
+    Setup,
+    Index,
+    ClocktowerOnline,
+    Bloodstar,
+    Markdown,
+    Text,
+    Changes,
+}
+
+impl Output {
+    /// Everything but the json for other tools and the text almanacs
+    pub const DEFAULT: [Output; 7] = [
+        Output::Json,
+        Output::Almanac,
+        Output::Sheet,
+        Output::NightSheet,
+        Output::Setup,
+        Output::Index,
+        Output::Changes,
+    ];
+}
+
+/// Lints the given scripts, or every script in the source directory, and writes `outputs` for
+/// each of them to `out_dir`
+///
+/// The index always lists every script, so building a few of them does not drop the rest. Script
+/// json is checked against the schema, and every violation is an error.
+pub fn build(
+    config: &Config,
+    database: &CharacterDatabase,
+    scripts: &[String],
+    outputs: &[Output],
+    out_dir: &str,
+    allowed: &[Lint],
+    diagnostics: &mut Diagnostics,
+) {
+    let wants = |output| outputs.contains(&output);
+
+    let SpecialCharacters {
+        dusk,
+        minions,
+        demon,
+        dawn,
+    } = special_characters(&database.night_order, &database.characters);
+    let first_night_special = [&dusk, &minions, &demon, &dawn];
+    let other_night_special = [&dusk, &dawn];
+
+    if let Err(err) = create_dir_all(out_dir) {
+        diagnostics.error(out_dir, 0, 0, format!("Failed to create out dir: {err}"));
+        return;
+    }
+    let out_dir = Path::new(out_dir);
+
+    let schema = if wants(Output::Json) {
+        read_schema(&config.input.schema, diagnostics)
+    } else {
+        None
+    };
+
+    let mut index_entries = vec![];
+    let mut cache = ScriptCache::default();
+
+    for source in config.input.script_list(scripts, diagnostics) {
+        let file_name = file_name(Path::new(&source));
+        let Some(script) = database.prepare_script(&source, &mut cache, diagnostics) else {
+            continue;
+        };
+        script.lint(allowed, diagnostics);
+        script.check_jinxes(&database.characters, diagnostics);
+
+        let mut write = |name: String, what: &str, write: &dyn Fn(&mut File) -> io::Result<()>| {
+            let path = out_dir.join(name);
+            if let Err(err) = File::create(&path).and_then(|mut file| write(&mut file)) {
+                diagnostics.error(&path, 0, 0, format!("Failed to write {what} file: {err}"));
+            }
+        };
+
+        if wants(Output::Json) {
+            write(config.output.json(&file_name), "script", &|file| {
+                script.write_json(file, &file_name, config)
+            });
+        }
+        if wants(Output::ClocktowerOnline) {
+            write(
+                config.output.clocktower_online(&file_name),
+                "script",
+                &|file| script.write_clocktower_online(file, &file_name, config),
+            );
+        }
+        if wants(Output::Bloodstar) {
+            write(config.output.bloodstar(&file_name), "script", &|file| {
+                script.write_bloodstar(file, &file_name, config)
+            });
+        }
+        if wants(Output::Almanac) {
+            write(config.output.almanac(&file_name), "almanac", &|file| {
+                script.write_html(file, &first_night_special, &other_night_special)
+            });
+        }
+        if wants(Output::Markdown) {
+            write(config.output.markdown(&file_name), "almanac", &|file| {
+                script.write_almanac_text(file, Markup::Markdown)
+            });
+            for character in &script.characters {
+                write(
+                    config.output.character_markdown(&file_name, &character.id),
+                    "almanac",
+                    &|file| script.write_character_text(file, character, Markup::Markdown),
+                );
+            }
+        }
+        if wants(Output::Text) {
+            write(config.output.text(&file_name), "almanac", &|file| {
+                script.write_almanac_text(file, Markup::Plain)
+            });
+            for character in &script.characters {
+                write(
+                    config.output.character_text(&file_name, &character.id),
+                    "almanac",
+                    &|file| script.write_character_text(file, character, Markup::Plain),
+                );
+            }
+        }
+        if wants(Output::Changes) {
+            write(
+                config.output.changes(&file_name),
+                "change report",
+                &|file| script.write_change_report(file, &database.characters),
+            );
+            write(
+                config.output.changes_json(&file_name),
+                "change report",
+                &|file| script.write_change_report_json(file, &database.characters),
+            );
+        }
+        if wants(Output::Setup) {
+            write(config.output.setup(&file_name), "setup", &|file| {
+                script.write_setup_json(file)
+            });
+        }
+        for paper in Paper::ALL {
+            if wants(Output::Sheet) {
+                write(
+                    config.output.sheet(&file_name, paper),
+                    "character sheet",
+                    &|file| script.write_sheet(file, paper),
+                );
+            }
+            if wants(Output::NightSheet) {
+                write(
+                    config.output.night_sheet(&file_name, paper),
+                    "night sheet",
+                    &|file| {
+                        script.write_night_sheet(
+                            file,
+                            paper,
+                            &first_night_special,
+                            &other_night_special,
+                        )
+                    },
+                );
+            }
+        }
+        if let Some(schema) = &schema {
+            let path = out_dir.join(config.output.json(&file_name));
+            validate(&path.to_string_lossy(), schema, diagnostics);
+        }
+        index_entries.push(IndexEntry {
+            file_name,
+            name: script.name,
+            base: script.base,
+        });
+    }
+
+    if wants(Output::Index) {
+        if !scripts.is_empty() {
+            index_entries = all_index_entries(config, database, &mut cache, diagnostics);
+        }
+        let index_path = out_dir.join(&config.output.index);
+        if let Err(err) = File::create(&index_path)
+            .and_then(|mut file| write_index(&mut file, &index_entries, config))
+        {
+            diagnostics.error(
+                &index_path,
+                0,
+                0,
+                format!("Failed to write index file: {err}"),
+            );
+        }
+    }
+}
+
+/// Index entries for every script in the source directory, errors are left to their own builds
+fn all_index_entries(
+    config: &Config,
+    database: &CharacterDatabase,
+    cache: &mut ScriptCache,
+    diagnostics: &mut Diagnostics,
+) -> Vec<IndexEntry> {
+    config
+        .input
+        .script_sources(diagnostics)
+        .into_iter()
+        .filter_map(|source| {
+            let script = Script::from_source(
+                &source,
+                &database.characters,
+                cache,
+                &mut Diagnostics::default(),
+            )?;
+            Some(IndexEntry {
+                file_name: file_name(Path::new(&source)),
+                name: script.name,
+                base: script.base,
+            })
+        })
+        .collect()
+}
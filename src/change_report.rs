@@ -0,0 +1,244 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+};
+
+use serde::Serialize;
+
+use crate::{
+    character::{Character, Jinx},
+    jinx::unpatched,
+    patch::{IgnoredPatch, PatchReason},
+    script::Script,
+};
+
+#[derive(Debug, Serialize)]
+pub struct ChangeReport {
+    pub script: String,
+    pub patched: Vec<CharacterChange>,
+    pub ignored: Vec<IgnoredChange>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CharacterChange {
+    pub id: String,
+    pub name: String,
+    /// `direct` or `jinx`
+    pub reason: &'static str,
+    /// The character whose jinx caused the patch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub via: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ability: Option<TextChange>,
+    /// Jinxes by id with the `patched_` prefix left off, rules have the character's own id
+    #[serde(rename = "jinxesAdded", skip_serializing_if = "Vec::is_empty")]
+    pub jinxes_added: Vec<Jinx>,
+    #[serde(rename = "jinxesRemoved", skip_serializing_if = "Vec::is_empty")]
+    pub jinxes_removed: Vec<Jinx>,
+    #[serde(rename = "jinxesChanged", skip_serializing_if = "Vec::is_empty")]
+    pub jinxes_changed: Vec<JinxChange>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TextChange {
+    pub official: String,
+    pub patched: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JinxChange {
+    pub id: String,
+    pub official: String,
+    pub patched: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IgnoredChange {
+    pub id: String,
+    /// `bootlegger` or `override`
+    pub source: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<String>,
+    pub reason: String,
+}
+
+impl Script {
+    /// Compares the patched characters to their data in `character_list`
+    pub fn change_report(&self, character_list: &HashMap<String, Character>) -> ChangeReport {
+        let patched = self
+            .patch_log
+            .patched
+            .iter()
+            .filter_map(|(id, reason)| {
+                let character = self
+                    .characters
+                    .iter()
+                    .find(|character| character.id == *id)?;
+                let official = character_list.get(unpatched(id))?;
+                Some(change(character, official, reason))
+            })
+            .collect();
+        let ignored = self.patch_log.ignored.iter().map(ignored).collect();
+
+        ChangeReport {
+            script: self.name.to_owned(),
+            patched,
+            ignored,
+        }
+    }
+
+    pub fn write_change_report<T>(
+        &self,
+        writer: &mut T,
+        character_list: &HashMap<String, Character>,
+    ) -> io::Result<()>
+    where
+        T: Write,
+    {
+        let report = self.change_report(character_list);
+        let mut out = vec![format!("Changes to {}", report.script)];
+
+        if report.patched.is_empty() {
+            out.push(String::from("No characters are patched."));
+        }
+        for change in &report.patched {
+            let mut lines = vec![match &change.via {
+                Some(via) => format!(
+                    "{} ({}), through its jinx with {via}",
+                    change.name, change.id
+                ),
+                None => format!("{} ({}), patched directly", change.name, change.id),
+            }];
+            if let Some(ability) = &change.ability {
+                lines.push(format!("  - ability: {}", ability.official));
+                lines.push(format!("  + ability: {}", ability.patched));
+            }
+            for jinx in &change.jinxes_removed {
+                lines.push(format!(
+                    "  - {}: {}",
+                    jinx_label(change, &jinx.id),
+                    jinx.reason
+                ));
+            }
+            for jinx in &change.jinxes_added {
+                lines.push(format!(
+                    "  + {}: {}",
+                    jinx_label(change, &jinx.id),
+                    jinx.reason
+                ));
+            }
+            for jinx in &change.jinxes_changed {
+                let label = jinx_label(change, &jinx.id);
+                lines.push(format!("  - {label}: {}", jinx.official));
+                lines.push(format!("  + {label}: {}", jinx.patched));
+            }
+            out.push(lines.join("\n"));
+        }
+
+        if !report.ignored.is_empty() {
+            let lines: Vec<String> = report
+                .ignored
+                .iter()
+                .map(|ignored| match &ignored.line {
+                    Some(line) => format!(
+                        "  {} {}: {line} ({})",
+                        ignored.source, ignored.id, ignored.reason
+                    ),
+                    None => format!("  {} {} ({})", ignored.source, ignored.id, ignored.reason),
+                })
+                .collect();
+            out.push(format!("Ignored\n{}", lines.join("\n")));
+        }
+
+        writeln!(writer, "{}", out.join("\n\n"))?;
+
+        Ok(())
+    }
+
+    pub fn write_change_report_json<T>(
+        &self,
+        writer: &mut T,
+        character_list: &HashMap<String, Character>,
+    ) -> io::Result<()>
+    where
+        T: Write,
+    {
+        serde_json::to_writer(writer, &self.change_report(character_list))?;
+        Ok(())
+    }
+}
+
+fn change(character: &Character, official: &Character, reason: &PatchReason) -> CharacterChange {
+    let (reason, via) = match reason {
+        PatchReason::Direct => ("direct", None),
+        PatchReason::Jinx(with) => ("jinx", Some(unpatched(with).to_owned())),
+    };
+    let ability = (character.ability != official.ability).then(|| TextChange {
+        official: official.ability.to_owned(),
+        patched: character.ability.to_owned(),
+    });
+
+    let own_id = unpatched(&character.id);
+    let normalise = |jinx: &Jinx| Jinx {
+        id: unpatched(&jinx.id).to_owned(),
+        reason: jinx.reason.to_owned(),
+    };
+    let patched: Vec<Jinx> = character.jinxes.iter().map(normalise).collect();
+    let official: Vec<Jinx> = official.jinxes.iter().map(normalise).collect();
+    let find = |jinxes: &[Jinx], jinx: &Jinx| {
+        jinxes
+            .iter()
+            .find(|other| {
+                other.id == jinx.id && (other.id != own_id || other.reason == jinx.reason)
+            })
+            .map(|other| other.reason.to_owned())
+    };
+
+    let mut jinxes_added = vec![];
+    let mut jinxes_changed = vec![];
+    for jinx in &patched {
+        match find(&official, jinx) {
+            None => jinxes_added.push(jinx.to_owned()),
+            Some(reason) if reason != jinx.reason => jinxes_changed.push(JinxChange {
+                id: jinx.id.to_owned(),
+                official: reason,
+                patched: jinx.reason.to_owned(),
+            }),
+            Some(_) => (),
+        }
+    }
+    let jinxes_removed = official
+        .iter()
+        .filter(|jinx| find(&patched, jinx).is_none())
+        .cloned()
+        .collect();
+
+    CharacterChange {
+        id: character.id.to_owned(),
+        name: character.name.to_owned(),
+        reason,
+        via,
+        ability,
+        jinxes_added,
+        jinxes_removed,
+        jinxes_changed,
+    }
+}
+
+fn ignored(ignored: &IgnoredPatch) -> IgnoredChange {
+    IgnoredChange {
+        id: ignored.id.to_owned(),
+        source: ignored.source.to_string(),
+        line: ignored.line.to_owned(),
+        reason: ignored.reason.to_owned(),
+    }
+}
+
+/// Rules are stored as jinxes with the character itself
+fn jinx_label(change: &CharacterChange, id: &str) -> String {
+    if id == unpatched(&change.id) {
+        String::from("rule")
+    } else {
+        format!("jinx with {id}")
+    }
+}
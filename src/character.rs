@@ -1,8 +1,14 @@
-use std::{collections::HashMap, fs::File, io::Read, path::Path};
+use std::{fs::File, io::Read, path::Path, str::FromStr};
 
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 
+use crate::{
+    config::Config,
+    diagnostics::Diagnostics,
+    night_order::{parse_wakes, Wake},
+};
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Character {
     pub id: String,
@@ -63,6 +69,9 @@ pub struct Character {
     pub jinxes: Vec<Jinx>,
     #[serde(skip)]
     pub required_characters: Vec<String>,
+    /// Night order constraints from `wakes` lines, resolved once every character is loaded
+    #[serde(skip)]
+    pub wakes: Vec<Wake>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Default, PartialEq, Eq, Clone, PartialOrd, Ord)]
@@ -103,45 +112,53 @@ pub struct Jinx {
 impl Character {
     pub fn from_source(
         source_path: &Path,
-        character_list: &HashMap<String, Character>,
-    ) -> Character {
-        let source = source_path
+        config: &Config,
+        diagnostics: &mut Diagnostics,
+    ) -> Option<Character> {
+        let Some(source) = source_path
             .file_stem()
-            .unwrap()
-            .to_ascii_lowercase()
-            .into_string()
-            .unwrap();
+            .and_then(|stem| stem.to_ascii_lowercase().into_string().ok())
+        else {
+            diagnostics.error(source_path, 0, 0, "Invalid character file name");
+            return None;
+        };
         let mut buf = String::new();
-        File::open(source_path)
-            .unwrap_or_else(|_| {
-                panic!("Failed to open character source file for character {source}",)
-            })
-            .read_to_string(&mut buf)
-            .unwrap_or_else(|_| {
-                panic!("Failed to read character source file for character {source}",)
-            });
-
-        let mut lines = buf.lines();
-
-        let name = lines
-            .next()
-            .unwrap_or_else(|| panic!("Character {source} does not have a name"))
-            .to_owned();
-        let team = lines
-            .next()
-            .unwrap_or_else(|| panic!("Character {source} does not have a team"))
-            .to_owned();
-        let ability = lines
-            .next()
-            .unwrap_or_else(|| panic!("Character {source} does not have an ability"))
-            .to_owned();
+        if let Err(err) = File::open(source_path).and_then(|mut file| file.read_to_string(&mut buf))
+        {
+            diagnostics.error(
+                source_path,
+                0,
+                0,
+                format!("Failed to read character source file: {err}"),
+            );
+            return None;
+        }
+
+        let mut lines = buf.lines().enumerate().map(|(idx, line)| (idx + 1, line));
+
+        let Some((_, name)) = lines.next() else {
+            diagnostics.error(source_path, 1, 1, "Character does not have a name");
+            return None;
+        };
+        let Some((team_line, team)) = lines.next() else {
+            diagnostics.error(source_path, 2, 1, "Character does not have a team");
+            return None;
+        };
+        let Some((_, ability)) = lines.next() else {
+            diagnostics.error(source_path, 3, 1, "Character does not have an ability");
+            return None;
+        };
+        let name = name.to_owned();
+        let team = team.parse().unwrap_or_else(|err| {
+            diagnostics.error(source_path, team_line, 1, err);
+            Team::default()
+        });
+        let ability = ability.to_owned();
 
         let mut reminders = vec![];
         let mut reminders_global = vec![];
         let mut first_night_reminder = String::new();
         let mut other_night_reminder = String::new();
-        let mut first_night = 0f32;
-        let mut other_night = 0f32;
         let mut setup = false;
         let mut flavour = String::new();
         let mut overview_short = String::new();
@@ -153,19 +170,22 @@ impl Character {
         let mut special = AppSpecial::default();
         let mut jinxes = vec![];
         let mut required_characters = vec![];
+        let mut wakes = vec![];
         let img_path = Path::new(source_path.parent().unwrap()).join(format!("{source}.png"));
         let mut image = if img_path.exists() {
-            let mut components = img_path.components();
-            components.next();
-            components.next();
+            let relative = img_path
+                .strip_prefix(&config.input.character_dir)
+                .unwrap_or(&img_path);
             vec![format!(
-                "https://raw.githubusercontent.com/Totox00/botc-scripts/refs/heads/main/script-gen/characters/{}", components.as_path().to_str().unwrap()
+                "{}{}",
+                config.urls.character_images,
+                relative.to_string_lossy()
             )]
         } else {
             vec![]
         };
 
-        while let Some(line) = lines.next() {
+        while let Some((line_no, line)) = lines.next() {
             match line {
                 "setup" => setup = true,
                 "bagdisabled" => special.bag_disabled = true,
@@ -174,7 +194,7 @@ impl Character {
                 "replacereveal" => special.replace_reveal = true,
                 "grimoire_global_demon" => special.grimoire_global_demon = true,
                 "attribution" => {
-                    for line in lines.by_ref() {
+                    for (_, line) in lines.by_ref() {
                         if line.is_empty() {
                             break;
                         } else {
@@ -183,7 +203,7 @@ impl Character {
                     }
                 }
                 "flavour" | "flavor" => {
-                    for line in lines.by_ref() {
+                    for (_, line) in lines.by_ref() {
                         if line.is_empty() {
                             break;
                         } else {
@@ -193,7 +213,7 @@ impl Character {
                     }
                 }
                 "examples" => {
-                    for line in lines.by_ref() {
+                    for (_, line) in lines.by_ref() {
                         if line.is_empty() {
                             break;
                         } else {
@@ -202,7 +222,7 @@ impl Character {
                     }
                 }
                 "howtorun" => {
-                    for line in lines.by_ref() {
+                    for (_, line) in lines.by_ref() {
                         if line.is_empty() {
                             break;
                         } else {
@@ -211,7 +231,7 @@ impl Character {
                     }
                 }
                 "advice" => {
-                    for line in lines.by_ref() {
+                    for (_, line) in lines.by_ref() {
                         if line.is_empty() {
                             break;
                         } else {
@@ -219,103 +239,109 @@ impl Character {
                         }
                     }
                 }
+                "" => (),
                 _ => {
-                    if let Some((key, value)) = line.split_once(' ') {
-                        match key {
-                            "reminder" => {
-                                if let Some((count, value)) = value.split_once(' ') {
-                                    for _ in 0..count.parse().unwrap_or_else(|_| {
-                                        panic!("Reminder for {source} does not have a count")
-                                    }) {
-                                        reminders.push(value.to_owned());
-                                    }
-                                }
-                            }
-                            "globalreminder" => {
-                                if let Some((count, value)) = value.split_once(' ') {
-                                    for _ in 0..count.parse().unwrap_or_else(|_| {
-                                        panic!("Reminder for {source} does not have a count")
-                                    }) {
-                                        reminders_global.push(value.to_owned());
-                                    }
-                                }
-                            }
-                            "image" => image = value.split(' ').map(String::from).collect(),
-                            "firstnight" => first_night_reminder = value.to_owned(),
-                            "othernight" => other_night_reminder = value.to_owned(),
-                            "everynight" => {
-                                first_night_reminder = value.to_owned();
-                                other_night_reminder = value.to_owned();
-                            }
-                            "wakes" => {
-                                let mut split = value.split(' ');
-                                let night = split.next().unwrap_or_else(|| {
-                                    panic!("Waking pattern for {source} is missing night")
-                                });
-                                let relation = split.next().unwrap_or_else(|| {
-                                    panic!("Waking pattern for {source} is missing relation")
-                                });
-                                let other_id = split.next().unwrap_or_else(|| {
-                                    panic!("Waking pattern for {source} is missing other id")
-                                });
+                    let Some((key, value)) = line.split_once(' ') else {
+                        diagnostics.warning(
+                            source_path,
+                            line_no,
+                            1,
+                            format!("Unrecognised line {line}"),
+                        );
+                        continue;
+                    };
+                    let value_column = key.len() + 2;
 
-                                let other_char = character_list.get(other_id).unwrap_or_else(|| panic!("Could not find character with id {other_id} for character {source}"));
-                                let offset = match relation {
-                                    "before" => -0.1,
-                                    "after" => 0.1,
-                                    _ => panic!("Invalid relation for character {source}"),
-                                };
-
-                                match night {
-                                    "first" => first_night = other_char.first_night + offset,
-                                    "other" => other_night = other_char.other_night + offset,
-                                    "every" => {
-                                        first_night = other_char.first_night + offset;
-                                        other_night = other_char.other_night + offset;
-                                    }
-                                    _ => panic!("Invalid night for character {source}"),
+                    match key {
+                        "reminder" | "globalreminder" => {
+                            let (count, value) = match parse_reminder(value) {
+                                Ok(reminder) => reminder,
+                                Err(err) => {
+                                    diagnostics.error(source_path, line_no, value_column, err);
+                                    continue;
                                 }
+                            };
+                            let list = if key == "reminder" {
+                                &mut reminders
+                            } else {
+                                &mut reminders_global
+                            };
+                            for _ in 0..count {
+                                list.push(value.to_owned());
                             }
-                            "overview" => {
-                                overview_short = value.to_owned();
-                                for line in lines.by_ref() {
-                                    if line.is_empty() {
-                                        break;
-                                    } else {
-                                        overview_long.push(line.to_string());
-                                    }
+                        }
+                        "image" => image = value.split(' ').map(String::from).collect(),
+                        "firstnight" => first_night_reminder = value.to_owned(),
+                        "othernight" => other_night_reminder = value.to_owned(),
+                        "everynight" => {
+                            first_night_reminder = value.to_owned();
+                            other_night_reminder = value.to_owned();
+                        }
+                        "wakes" => match parse_wakes(value) {
+                            Ok((nights, relation, offset)) => {
+                                for night in nights {
+                                    wakes.push(Wake {
+                                        night,
+                                        relation: relation.clone(),
+                                        path: source_path.to_owned(),
+                                        line: line_no,
+                                        column: value_column + offset,
+                                    });
                                 }
                             }
-                            "requires" => required_characters.push(value.to_owned()),
-                            "card" => special.cards.push(value.to_owned()),
-                            "jinx" => {
-                                if let Some((id, reason)) = value.split_once(' ') {
-                                    jinxes.push(Jinx {
-                                        id: id.to_owned(),
-                                        reason: reason.to_owned(),
-                                    });
+                            Err((offset, message)) => diagnostics.error(
+                                source_path,
+                                line_no,
+                                value_column + offset,
+                                message,
+                            ),
+                        },
+                        "overview" => {
+                            overview_short = value.to_owned();
+                            for (_, line) in lines.by_ref() {
+                                if line.is_empty() {
+                                    break;
                                 } else {
-                                    panic!("Invalid jinx for {source}");
+                                    overview_long.push(line.to_string());
                                 }
                             }
-                            _ => panic!("Invalid key {key} in character {source}"),
+                        }
+                        "requires" => required_characters.push(value.to_owned()),
+                        "card" => special.cards.push(value.to_owned()),
+                        "jinx" => {
+                            if let Some((id, reason)) = value.split_once(' ') {
+                                jinxes.push(Jinx {
+                                    id: id.to_owned(),
+                                    reason: reason.to_owned(),
+                                });
+                            } else {
+                                diagnostics.error(
+                                    source_path,
+                                    line_no,
+                                    value_column,
+                                    "Jinx must be <id> <reason>",
+                                );
+                            }
+                        }
+                        _ => {
+                            diagnostics.error(source_path, line_no, 1, format!("Invalid key {key}"))
                         }
                     }
                 }
             }
         }
 
-        Character {
+        Some(Character {
             id: source,
             name,
-            team: Team::from(team.as_str()),
+            team,
             ability,
             reminders,
             reminders_global,
             first_night_reminder,
             other_night_reminder,
-            first_night,
-            other_night,
+            first_night: 0.0,
+            other_night: 0.0,
             setup,
             official: false,
             patched: false,
@@ -328,27 +354,44 @@ impl Character {
             attribution,
             image,
             required_characters,
+            wakes,
             special: if special.any() {
                 Some(special.as_serializable())
             } else {
                 None
             },
             jinxes,
-        }
+        })
+    }
+}
+
+impl Character {
+    /// Whether the app special list has a `selection` entry, like `bag-disabled`
+    pub fn has_selection(&self, name: &str) -> bool {
+        let Some(Value::Array(special)) = &self.special else {
+            return false;
+        };
+
+        special.iter().any(|entry| {
+            entry.get("type").and_then(Value::as_str) == Some("selection")
+                && entry.get("name").and_then(Value::as_str) == Some(name)
+        })
     }
 }
 
-impl From<&str> for Team {
-    fn from(value: &str) -> Self {
+impl FromStr for Team {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
         match value {
-            "Townsfolk" => Team::Townsfolk,
-            "Outsider" => Team::Outsider,
-            "Minion" => Team::Minion,
-            "Demon" => Team::Demon,
-            "Traveller" => Team::Traveller,
-            "Fabled" => Team::Fabled,
-            "Special" => Team::Special,
-            _ => panic!("Invalid team {value}"),
+            "Townsfolk" => Ok(Team::Townsfolk),
+            "Outsider" => Ok(Team::Outsider),
+            "Minion" => Ok(Team::Minion),
+            "Demon" => Ok(Team::Demon),
+            "Traveller" => Ok(Team::Traveller),
+            "Fabled" => Ok(Team::Fabled),
+            "Special" => Ok(Team::Special),
+            _ => Err(format!("Invalid team {value}")),
         }
     }
 }
@@ -444,3 +487,41 @@ fn is_zero(n: &f32) -> bool {
 fn is_false(b: &bool) -> bool {
     !b
 }
+
+/// Reads the `<count> <text>` of a `reminder` or `globalreminder` line
+pub fn parse_reminder(value: &str) -> Result<(usize, &str), String> {
+    let Some((count, text)) = value.split_once(' ') else {
+        return Err(String::from("Reminder does not have a count"));
+    };
+    let count = count
+        .parse()
+        .map_err(|_| format!("Invalid reminder count {count}"))?;
+    Ok((count, text))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::Character;
+
+    /// A character with only the fields the test cares about
+    pub fn character(id: &str, name: &str, team: &str, ability: &str) -> Character {
+        serde_json::from_value(json!({
+            "id": id,
+            "name": name,
+            "team": team,
+            "ability": ability,
+        }))
+        .unwrap()
+    }
+
+    pub fn character_list(characters: Vec<Character>) -> HashMap<String, Character> {
+        characters
+            .into_iter()
+            .map(|character| (character.id.clone(), character))
+            .collect()
+    }
+}
@@ -0,0 +1,294 @@
+use std::{
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{diagnostics::Diagnostics, lint::Lint, sheet::Paper};
+
+pub const CONFIG_PATH: &str = "script-gen.toml";
+
+/// Every key is optional and defaults to the layout of this repository
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub urls: UrlConfig,
+    pub input: InputConfig,
+    pub output: OutputConfig,
+    pub lints: LintConfig,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UrlConfig {
+    /// Where the generated almanacs are hosted, used for the `almanac` link in script json
+    pub almanac: String,
+    /// Where the images next to `.char` files are hosted, mirroring the characters directory
+    pub character_images: String,
+    /// Where the images listed in `official-images` are hosted
+    pub official_images: String,
+    /// Prefix of the links on the index page
+    pub index: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InputConfig {
+    pub characters: String,
+    pub official_images: String,
+    pub night_order: String,
+    pub schema: String,
+    pub character_dir: String,
+    pub bootlegger_dir: String,
+    pub source_dir: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    /// Where `build` writes to unless told otherwise
+    pub dir: String,
+    pub json: String,
+    pub almanac: String,
+    pub markdown: String,
+    pub text: String,
+    /// Pages of single characters, where `{character}` is the character id
+    pub character_markdown: String,
+    pub character_text: String,
+    pub sheet: String,
+    pub night_sheet: String,
+    pub setup: String,
+    pub clocktower_online: String,
+    pub bloodstar: String,
+    pub changes: String,
+    pub changes_json: String,
+    pub index: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LintConfig {
+    /// Lints to skip for every script
+    pub allow: Vec<String>,
+}
+
+impl Default for UrlConfig {
+    fn default() -> Self {
+        Self {
+            almanac: String::from("https://totox00.github.io/botc-scripts/"),
+            character_images: String::from(
+                "https://raw.githubusercontent.com/Totox00/botc-scripts/refs/heads/main/script-gen/characters/",
+            ),
+            official_images: String::from("https://botc.app/assets/"),
+            index: String::from("/botc-scripts/"),
+        }
+    }
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self {
+            characters: String::from("characters.json"),
+            official_images: String::from("official-images"),
+            night_order: String::from("night-order.json"),
+            schema: String::from("schema.json"),
+            character_dir: String::from("script-gen/characters"),
+            bootlegger_dir: String::from("script-gen/bootlegger"),
+            source_dir: String::from("script-gen/source"),
+        }
+    }
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            dir: String::from("script-gen/dist"),
+            json: String::from("{name}.official.json"),
+            almanac: String::from("{name}.html"),
+            markdown: String::from("{name}.md"),
+            text: String::from("{name}.txt"),
+            character_markdown: String::from("{name}.{character}.md"),
+            character_text: String::from("{name}.{character}.txt"),
+            sheet: String::from("{name}.sheet-{paper}.html"),
+            night_sheet: String::from("{name}.night-{paper}.html"),
+            setup: String::from("{name}.setup.json"),
+            clocktower_online: String::from("{name}.clocktower-online.json"),
+            bloodstar: String::from("{name}.bloodstar.json"),
+            changes: String::from("{name}.changes.txt"),
+            changes_json: String::from("{name}.changes.json"),
+            index: String::from("index.html"),
+        }
+    }
+}
+
+impl Config {
+    /// Reads the config file if there is one, falling back to the defaults otherwise
+    pub fn load(path: &str, diagnostics: &mut Diagnostics) -> Option<Config> {
+        if !Path::new(path).exists() {
+            return Some(Config::default());
+        }
+
+        let mut buf = String::new();
+        if let Err(err) = File::open(path).and_then(|mut file| file.read_to_string(&mut buf)) {
+            diagnostics.error(path, 0, 0, format!("Failed to read config file: {err}"));
+            return None;
+        }
+
+        match toml::from_str(&buf) {
+            Ok(config) => Some(config),
+            Err(err) => {
+                let (line, column) = err
+                    .span()
+                    .map(|span| line_column(&buf, span.start))
+                    .unwrap_or_default();
+                diagnostics.error(path, line, column, err.message());
+                None
+            }
+        }
+    }
+}
+
+impl InputConfig {
+    /// Resolves a script argument, which is either a path or the name of a file in the source directory
+    pub fn script_path(&self, script: &str) -> String {
+        if Path::new(script).exists() {
+            return script.to_owned();
+        }
+        let in_source_dir = Path::new(&self.source_dir).join(script);
+        if in_source_dir.exists() {
+            in_source_dir.to_string_lossy().into_owned()
+        } else {
+            script.to_owned()
+        }
+    }
+
+    /// Every file in the source directory, sorted
+    pub fn script_sources(&self, diagnostics: &mut Diagnostics) -> Vec<String> {
+        let dir = match Path::new(&self.source_dir).read_dir() {
+            Ok(dir) => dir,
+            Err(err) => {
+                diagnostics.error(
+                    &self.source_dir,
+                    0,
+                    0,
+                    format!("Failed to read source dir: {err}"),
+                );
+                return vec![];
+            }
+        };
+        let mut sources: Vec<PathBuf> = dir
+            .flatten()
+            .filter(|entry| entry.file_type().is_ok_and(|f| f.is_file()))
+            .map(|entry| entry.path())
+            .collect();
+        sources.sort();
+        sources
+            .into_iter()
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    /// Resolves the script arguments, or every script in the source directory if there are none
+    pub fn script_list(&self, scripts: &[String], diagnostics: &mut Diagnostics) -> Vec<String> {
+        if scripts.is_empty() {
+            self.script_sources(diagnostics)
+        } else {
+            scripts
+                .iter()
+                .map(|script| self.script_path(script))
+                .collect()
+        }
+    }
+}
+
+impl OutputConfig {
+    pub fn json(&self, name: &str) -> String {
+        self.json.replace("{name}", name)
+    }
+
+    pub fn almanac(&self, name: &str) -> String {
+        self.almanac.replace("{name}", name)
+    }
+
+    pub fn markdown(&self, name: &str) -> String {
+        self.markdown.replace("{name}", name)
+    }
+
+    pub fn text(&self, name: &str) -> String {
+        self.text.replace("{name}", name)
+    }
+
+    pub fn character_markdown(&self, name: &str, character: &str) -> String {
+        self.character_markdown
+            .replace("{name}", name)
+            .replace("{character}", character)
+    }
+
+    pub fn character_text(&self, name: &str, character: &str) -> String {
+        self.character_text
+            .replace("{name}", name)
+            .replace("{character}", character)
+    }
+
+    pub fn setup(&self, name: &str) -> String {
+        self.setup.replace("{name}", name)
+    }
+
+    pub fn clocktower_online(&self, name: &str) -> String {
+        self.clocktower_online.replace("{name}", name)
+    }
+
+    pub fn bloodstar(&self, name: &str) -> String {
+        self.bloodstar.replace("{name}", name)
+    }
+
+    pub fn changes(&self, name: &str) -> String {
+        self.changes.replace("{name}", name)
+    }
+
+    pub fn changes_json(&self, name: &str) -> String {
+        self.changes_json.replace("{name}", name)
+    }
+
+    pub fn sheet(&self, name: &str, paper: Paper) -> String {
+        self.sheet
+            .replace("{name}", name)
+            .replace("{paper}", paper.name())
+    }
+
+    pub fn night_sheet(&self, name: &str, paper: Paper) -> String {
+        self.night_sheet
+            .replace("{name}", name)
+            .replace("{paper}", paper.name())
+    }
+}
+
+impl LintConfig {
+    /// The lints allowed for every script, errors are reported against the config file at `path`
+    pub fn allowed(&self, path: &str, diagnostics: &mut Diagnostics) -> Vec<Lint> {
+        self.allow
+            .iter()
+            .filter_map(|lint| match lint.parse() {
+                Ok(lint) => Some(lint),
+                Err(err) => {
+                    diagnostics.error(path, 0, 0, err);
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .chars()
+        .rev()
+        .take_while(|char| *char != '\n')
+        .count()
+        + 1;
+    (line, column)
+}
@@ -0,0 +1,141 @@
+use std::{collections::HashMap, fs::File, io::Read, path::Path};
+
+use crate::{
+    character::Character,
+    config::Config,
+    diagnostics::Diagnostics,
+    night_order::{resolve_night_order, NightOrder, NightOrderMismatch},
+    patch::{read_patches, resolve_wakes, Patch},
+    script::{Script, ScriptCache},
+};
+
+/// The official characters from `characters.json` together with every `.char` character
+pub struct CharacterDatabase {
+    pub characters: HashMap<String, Character>,
+    /// Images of the official characters, which bootlegger patches carry over to the patched copy
+    pub images: HashMap<String, Vec<String>>,
+    pub patches: HashMap<String, Patch>,
+    pub night_order: NightOrder,
+    /// Official characters `characters.json` places differently from the night order file
+    pub night_order_mismatches: Vec<NightOrderMismatch>,
+}
+
+impl CharacterDatabase {
+    pub fn load(config: &Config, diagnostics: &mut Diagnostics) -> Option<CharacterDatabase> {
+        let mut images = HashMap::new();
+        if let Some(image_list_str) = read_file(&config.input.official_images, diagnostics) {
+            for image in image_list_str.lines().filter(|str| !str.is_empty()) {
+                let mut iter = image.split(' ');
+                let id = iter.next().unwrap().to_owned();
+                let character_images: Vec<String> = iter
+                    .map(|image| format!("{}{image}.webp", config.urls.official_images))
+                    .collect();
+
+                images.insert(id, character_images);
+            }
+        }
+        let character_list_str = read_file(&config.input.characters, diagnostics)?;
+        let official_characters = match serde_json::from_str::<Vec<Character>>(&character_list_str)
+        {
+            Ok(characters) => characters,
+            Err(err) => {
+                diagnostics.error(
+                    &config.input.characters,
+                    err.line(),
+                    err.column(),
+                    err.to_string(),
+                );
+                return None;
+            }
+        };
+        let mut characters = HashMap::new();
+        for mut character in official_characters {
+            character.official = true;
+            if let Some(image) = images.get(&character.id) {
+                character.image = image.clone();
+            }
+            characters.insert(character.id.clone(), character);
+        }
+
+        let night_order = NightOrder::read(&config.input.night_order, diagnostics)?;
+        let night_order_mismatches = night_order.apply(&mut characters);
+
+        load_dir(
+            Path::new(&config.input.character_dir),
+            &mut characters,
+            config,
+            diagnostics,
+        );
+
+        resolve_night_order(&mut characters, diagnostics);
+
+        let mut patches = read_patches(&config.input.bootlegger_dir, &characters, diagnostics);
+        resolve_wakes(&mut patches, &characters, diagnostics);
+
+        Some(CharacterDatabase {
+            characters,
+            images,
+            patches,
+            night_order,
+            night_order_mismatches,
+        })
+    }
+
+    /// Reads a script source and applies everything needed before it can be written out
+    pub fn prepare_script(
+        &self,
+        source: &str,
+        cache: &mut ScriptCache,
+        diagnostics: &mut Diagnostics,
+    ) -> Option<Script> {
+        let mut script = Script::from_source(source, &self.characters, cache, diagnostics)?;
+        self.prepare(&mut script, diagnostics);
+        Some(script)
+    }
+
+    /// Resolves required characters, applies bootlegger patches, adds the needed fabled and
+    /// reads the setup modifiers
+    pub fn prepare(&self, script: &mut Script, diagnostics: &mut Diagnostics) {
+        script.resolve_required(&self.characters, diagnostics);
+        resolve_wakes(&mut script.overrides, &self.characters, diagnostics);
+        script.apply_patches(&self.patches, &self.images);
+        script.add_fabled(&self.characters);
+        script.resolve_setup(&self.characters);
+    }
+}
+
+pub fn read_file(path: &str, diagnostics: &mut Diagnostics) -> Option<String> {
+    let mut buf = String::new();
+    match File::open(path).and_then(|mut file| file.read_to_string(&mut buf)) {
+        Ok(_) => Some(buf),
+        Err(err) => {
+            diagnostics.error(path, 0, 0, format!("Failed to read file: {err}"));
+            None
+        }
+    }
+}
+
+fn load_dir(
+    path: &Path,
+    character_list: &mut HashMap<String, Character>,
+    config: &Config,
+    diagnostics: &mut Diagnostics,
+) {
+    if let Ok(dir) = path.read_dir() {
+        for character_entry in dir.flatten() {
+            if character_entry.file_type().is_ok_and(|f| f.is_dir()) {
+                load_dir(&character_entry.path(), character_list, config, diagnostics);
+            } else if character_entry
+                .path()
+                .extension()
+                .is_some_and(|ext| ext == "char")
+            {
+                if let Some(character) =
+                    Character::from_source(&character_entry.path(), config, diagnostics)
+                {
+                    character_list.insert(character.id.clone(), character);
+                }
+            }
+        }
+    }
+}
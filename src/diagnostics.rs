@@ -0,0 +1,108 @@
+use std::{
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    entries: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn error<P, M>(&mut self, path: P, line: usize, column: usize, message: M)
+    where
+        P: AsRef<Path>,
+        M: Into<String>,
+    {
+        self.push(Severity::Error, path, line, column, message);
+    }
+
+    pub fn warning<P, M>(&mut self, path: P, line: usize, column: usize, message: M)
+    where
+        P: AsRef<Path>,
+        M: Into<String>,
+    {
+        self.push(Severity::Warning, path, line, column, message);
+    }
+
+    fn push<P, M>(&mut self, severity: Severity, path: P, line: usize, column: usize, message: M)
+    where
+        P: AsRef<Path>,
+        M: Into<String>,
+    {
+        self.entries.push(Diagnostic {
+            severity,
+            path: path.as_ref().to_owned(),
+            line,
+            column,
+            message: message.into(),
+        });
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.entries.iter()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.entries
+            .iter()
+            .any(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.entries
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == severity)
+            .count()
+    }
+
+    pub fn report(&self) {
+        for diagnostic in &self.entries {
+            eprintln!("{diagnostic}");
+        }
+
+        let errors = self.count(Severity::Error);
+        let warnings = self.count(Severity::Warning);
+        if errors > 0 || warnings > 0 {
+            eprintln!("{errors} error(s), {warnings} warning(s)");
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+
+        // Line 0 means the diagnostic applies to the file as a whole
+        if self.line == 0 {
+            write!(f, "{}: {severity}: {}", self.path.display(), self.message)
+        } else {
+            write!(
+                f,
+                "{}:{}:{}: {severity}: {}",
+                self.path.display(),
+                self.line,
+                self.column,
+                self.message
+            )
+        }
+    }
+}
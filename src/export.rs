@@ -0,0 +1,189 @@
+use std::io::{self, Write};
+
+use serde_json::{json, Value};
+
+use crate::{
+    character::Character,
+    config::Config,
+    markup::{render_as, Markup},
+    script::Script,
+};
+
+impl Script {
+    pub fn write_clocktower_online<T>(
+        &self,
+        writer: &mut T,
+        file_name: &str,
+        config: &Config,
+    ) -> io::Result<()>
+    where
+        T: Write,
+    {
+        let first_night = self.first_night_order(&[]);
+        let other_night = self.other_night_order(&[]);
+        let mut out: Vec<Value> = vec![self.meta(file_name, config)];
+
+        for character in &self.characters {
+            if character.official && !character.patched {
+                out.push(json!({ "id": character.id }));
+                continue;
+            }
+            let mut role = role(character);
+            role["edition"] = json!("custom");
+            for (key, order) in [("firstNight", &first_night), ("otherNight", &other_night)] {
+                if let Some(position) = rank(order, character) {
+                    role[key] = json!(position);
+                }
+            }
+            out.push(role);
+        }
+
+        serde_json::to_writer(writer, &out)?;
+        Ok(())
+    }
+
+    pub fn write_bloodstar<T>(
+        &self,
+        writer: &mut T,
+        file_name: &str,
+        config: &Config,
+    ) -> io::Result<()>
+    where
+        T: Write,
+    {
+        let project: String = self
+            .name
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .map(|char| char.to_ascii_lowercase())
+            .collect();
+        let is_custom = |id: &str| {
+            self.characters
+                .iter()
+                .any(|character| character.id == id && (!character.official || character.patched))
+        };
+        let project_id = |id: &str| {
+            if is_custom(id) {
+                format!("{id}_{project}")
+            } else {
+                id.to_owned()
+            }
+        };
+        let first_night = self.first_night_order(&[]);
+        let other_night = self.other_night_order(&[]);
+
+        let mut out: Vec<Value> = vec![self.meta(file_name, config)];
+
+        for character in &self.characters {
+            if !is_custom(&character.id) {
+                out.push(Value::String(character.id.to_owned()));
+                continue;
+            }
+            let mut role = role(character);
+            role["id"] = json!(project_id(&character.id));
+            if !character.attribution.is_empty() {
+                role["attribution"] = json!(character.attribution.join(" "));
+            }
+            for (key, order) in [("firstNight", &first_night), ("otherNight", &other_night)] {
+                if let Some(position) = rank(order, character) {
+                    role[key] = json!(position);
+                }
+            }
+            if !character.jinxes.is_empty() {
+                role["jinxes"] = character
+                    .jinxes
+                    .iter()
+                    .map(|jinx| json!({ "id": project_id(&jinx.id), "reason": jinx.reason }))
+                    .collect();
+            }
+            out.push(role);
+        }
+
+        serde_json::to_writer(writer, &out)?;
+        Ok(())
+    }
+}
+
+/// Where a character wakes in the script's night order, counting from 1, if it wakes at all
+fn rank(order: &[&Character], character: &Character) -> Option<usize> {
+    order
+        .iter()
+        .position(|other| other.id == character.id)
+        .map(|idx| idx + 1)
+}
+
+/// A homebrew character with only the fields both tools know, and a single image. Neither tool
+/// reads our markup, so the ability and flavour are plain text.
+fn role(character: &Character) -> Value {
+    let mut role = json!({
+        "id": character.id,
+        "name": character.name,
+        "team": character.team,
+        "ability": render_as(&character.ability, Markup::Plain),
+    });
+    if let Some(image) = character.image.first() {
+        role["image"] = json!(image);
+    }
+    for (key, value) in [
+        ("firstNightReminder", &character.first_night_reminder),
+        ("otherNightReminder", &character.other_night_reminder),
+        ("flavor", &render_as(&character.flavour, Markup::Plain)),
+    ] {
+        if !value.is_empty() {
+            role[key] = json!(value);
+        }
+    }
+    if !character.reminders.is_empty() {
+        role["reminders"] = json!(character.reminders);
+    }
+    if !character.reminders_global.is_empty() {
+        role["remindersGlobal"] = json!(character.reminders_global);
+    }
+    if character.setup {
+        role["setup"] = json!(true);
+    }
+
+    role
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use crate::{
+        character::tests::{character, character_list},
+        config::Config,
+        diagnostics::Diagnostics,
+        script::Script,
+    };
+
+    #[test]
+    fn clocktower_online_ranks_wakes_and_drops_markup() {
+        let mut seer = character("seer", "Seer", "townsfolk", "Learn a *secret*.");
+        seer.flavour = "_Who_ knows?".to_owned();
+        seer.first_night = 30.5;
+        let mut sleeper = character("sleeper", "Sleeper", "townsfolk", "You never wake.");
+        sleeper.flavour = "Zzz".to_owned();
+        let characters = character_list(vec![seer, sleeper]);
+        let script = Script::parse(
+            "test",
+            "Test\nTester\n\nseer\nsleeper",
+            &characters,
+            &mut Diagnostics::default(),
+        )
+        .unwrap();
+
+        let mut out = vec![];
+        script
+            .write_clocktower_online(&mut out, "test", &Config::default())
+            .unwrap();
+        let out: Vec<Value> = serde_json::from_slice(&out).unwrap();
+        let role = |id: &str| out.iter().find(|role| role["id"] == id).unwrap();
+
+        assert_eq!(role("seer")["ability"], "Learn a secret.");
+        assert_eq!(role("seer")["flavor"], "Who knows?");
+        assert_eq!(role("seer")["firstNight"], 1);
+        assert!(role("seer").get("otherNight").is_none());
+        assert!(role("sleeper").get("firstNight").is_none());
+    }
+}
@@ -0,0 +1,480 @@
+use std::{
+    collections::HashMap,
+    fs::{create_dir_all, File},
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use serde_json::{Map, Value};
+
+use crate::{
+    character::{Character, Team},
+    config::Config,
+    diagnostics::Diagnostics,
+    night_order::{pseudo_id, Night, NightOrder},
+};
+
+pub fn import(
+    source: &str,
+    character_list: &HashMap<String, Character>,
+    night_order: &NightOrder,
+    config: &Config,
+    diagnostics: &mut Diagnostics,
+) {
+    let mut buf = String::new();
+    if let Err(err) = File::open(source).and_then(|mut file| file.read_to_string(&mut buf)) {
+        diagnostics.error(source, 0, 0, format!("Failed to read script json: {err}"));
+        return;
+    }
+    let entries = match serde_json::from_str::<Vec<Value>>(&buf) {
+        Ok(entries) => entries,
+        Err(err) => {
+            diagnostics.error(source, err.line(), err.column(), err.to_string());
+            return;
+        }
+    };
+
+    let mut meta = None;
+    let mut ids = vec![];
+    let mut homebrew = vec![];
+    let mut id_map = HashMap::new();
+
+    for entry in &entries {
+        match entry {
+            Value::String(id) => ids.push(normalize_id(id)),
+            Value::Object(map) => {
+                let Some(raw_id) = map.get("id").and_then(Value::as_str) else {
+                    diagnostics.error(source, 0, 0, "Character without id");
+                    continue;
+                };
+                if raw_id == "_meta" {
+                    meta = Some(map);
+                } else if map.len() == 1 {
+                    ids.push(normalize_id(raw_id));
+                } else if let Some(official) = character_list
+                    .get(&normalize_id(raw_id))
+                    .filter(|character| character.official)
+                {
+                    if map.get("ability").and_then(Value::as_str) != Some(&official.ability) {
+                        diagnostics.warning(
+                            source,
+                            0,
+                            0,
+                            format!(
+                                "Character {} differs from the official character, use a bootlegger patch instead",
+                                official.id
+                            ),
+                        );
+                    }
+                    ids.push(official.id.clone());
+                } else {
+                    let id = homebrew_id(map, raw_id, &id_map);
+                    id_map.insert(raw_id.to_owned(), id.clone());
+                    ids.push(id);
+                    homebrew.push(map);
+                }
+            }
+            _ => diagnostics.error(source, 0, 0, format!("Invalid entry {entry}")),
+        }
+    }
+
+    let file_stem = Path::new(source)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(source)
+        .trim_end_matches(".official");
+    let name = meta
+        .and_then(|meta| meta.get("name"))
+        .and_then(Value::as_str)
+        .filter(|name| !name.is_empty())
+        .unwrap_or(file_stem);
+    let author = meta
+        .and_then(|meta| meta.get("author"))
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let script_id = script_id(name);
+
+    let mut out = format!("{name}\n{author}\n\n");
+    if let Some(Value::Array(rules)) = meta.and_then(|meta| meta.get("bootlegger")) {
+        for rule in rules.iter().filter_map(Value::as_str) {
+            out.push_str(&format!("bootlegger {rule}\n"));
+        }
+        out.push('\n');
+    }
+    for id in &ids {
+        if !character_list.contains_key(id) && !id_map.values().any(|other| other == id) {
+            diagnostics.warning(
+                source,
+                0,
+                0,
+                format!("Character {id} is not a known character"),
+            );
+        }
+        out.push_str(id);
+        out.push('\n');
+    }
+
+    let source_path = Path::new(&config.input.source_dir).join(&script_id);
+    if let Err(err) = write_new(&source_path, &out) {
+        diagnostics.error(
+            &source_path,
+            0,
+            0,
+            format!("Failed to write script source: {err}"),
+        );
+        return;
+    }
+
+    if homebrew.is_empty() {
+        return;
+    }
+
+    let character_dir = Path::new(&config.input.character_dir).join(&script_id);
+    for character in homebrew {
+        let raw_id = character["id"].as_str().unwrap();
+        let id = &id_map[raw_id];
+        if character_list.get(id).is_some_and(|other| !other.official) {
+            diagnostics.warning(
+                source,
+                0,
+                0,
+                format!("Character {id} already has a source file, skipping"),
+            );
+            continue;
+        }
+        let character_path = character_dir.join(format!("{id}.char"));
+        let out = character_source(character, &id_map, night_order, source, diagnostics);
+        if let Err(err) = write_new(&character_path, &out) {
+            diagnostics.error(
+                &character_path,
+                0,
+                0,
+                format!("Failed to write character source: {err}"),
+            );
+        }
+    }
+}
+
+/// Writes a character source with just the required lines, for the author to fill in
+pub fn new_character(
+    id: &str,
+    name: &str,
+    team: Team,
+    dir: Option<&str>,
+    character_list: &HashMap<String, Character>,
+    config: &Config,
+    diagnostics: &mut Diagnostics,
+) {
+    let id = normalize_id(id);
+    if character_list.contains_key(&id) {
+        diagnostics.error(
+            &config.input.character_dir,
+            0,
+            0,
+            format!("Character {id} already exists"),
+        );
+        return;
+    }
+
+    let mut character_dir = Path::new(&config.input.character_dir).to_owned();
+    if let Some(dir) = dir {
+        character_dir.push(dir);
+    }
+    let character_path = character_dir.join(format!("{id}.char"));
+    let team = match team {
+        Team::Townsfolk => "Townsfolk",
+        Team::Outsider => "Outsider",
+        Team::Minion => "Minion",
+        Team::Demon => "Demon",
+        Team::Traveller => "Traveller",
+        Team::Fabled => "Fabled",
+        Team::Special => "Special",
+    };
+    let out = format!("{name}\n{team}\nAbility\n");
+    match write_new(&character_path, &out) {
+        Ok(()) => println!("{}", character_path.display()),
+        Err(err) => diagnostics.error(
+            &character_path,
+            0,
+            0,
+            format!("Failed to write character source: {err}"),
+        ),
+    }
+}
+
+fn write_new(path: &Path, contents: &str) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+    File::create_new(path)?.write_all(contents.as_bytes())
+}
+
+pub fn normalize_id(id: &str) -> String {
+    id.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|char| char.to_ascii_lowercase())
+        .collect()
+}
+
+fn script_id(name: &str) -> String {
+    name.split(|char: char| char.is_whitespace() || char == '_')
+        .map(normalize_id)
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn homebrew_id(
+    character: &Map<String, Value>,
+    raw_id: &str,
+    id_map: &HashMap<String, String>,
+) -> String {
+    // App exports suffix homebrew ids with the edition, so prefer the name
+    let candidates = [
+        character
+            .get("name")
+            .and_then(Value::as_str)
+            .map(normalize_id),
+        raw_id
+            .split_once('_')
+            .map(|(prefix, _)| normalize_id(prefix)),
+    ];
+    candidates
+        .into_iter()
+        .flatten()
+        .find(|candidate| !candidate.is_empty() && !id_map.values().any(|other| other == candidate))
+        .unwrap_or_else(|| normalize_id(raw_id))
+}
+
+fn character_source(
+    character: &Map<String, Value>,
+    id_map: &HashMap<String, String>,
+    night_order: &NightOrder,
+    source: &str,
+    diagnostics: &mut Diagnostics,
+) -> String {
+    let str_field = |key: &str| {
+        character
+            .get(key)
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .replace('\n', " ")
+    };
+    let team = match character.get("team").and_then(Value::as_str) {
+        Some("townsfolk") => "Townsfolk",
+        Some("outsider") => "Outsider",
+        Some("minion") => "Minion",
+        Some("demon") => "Demon",
+        Some("traveller" | "traveler") => "Traveller",
+        Some("fabled") => "Fabled",
+        _ => "Special",
+    };
+
+    let mut out = format!(
+        "{}\n{team}\n{}\n\n",
+        str_field("name"),
+        str_field("ability")
+    );
+
+    match character.get("image") {
+        Some(Value::String(image)) => out.push_str(&format!("image {image}\n")),
+        Some(Value::Array(images)) => {
+            let images: Vec<_> = images.iter().filter_map(Value::as_str).collect();
+            if !images.is_empty() {
+                out.push_str(&format!("image {}\n", images.join(" ")));
+            }
+        }
+        _ => (),
+    }
+    for (key, field) in [
+        ("reminder", "reminders"),
+        ("globalreminder", "remindersGlobal"),
+    ] {
+        for (reminder, count) in count_reminders(character.get(field)) {
+            out.push_str(&format!("{key} {count} {reminder}\n"));
+        }
+    }
+
+    let first_night_reminder = str_field("firstNightReminder");
+    let other_night_reminder = str_field("otherNightReminder");
+    if !first_night_reminder.is_empty() && first_night_reminder == other_night_reminder {
+        out.push_str(&format!("everynight {first_night_reminder}\n"));
+    } else {
+        if !first_night_reminder.is_empty() {
+            out.push_str(&format!("firstnight {first_night_reminder}\n"));
+        }
+        if !other_night_reminder.is_empty() {
+            out.push_str(&format!("othernight {other_night_reminder}\n"));
+        }
+    }
+
+    let first_night = character.get("firstNight").and_then(Value::as_f64);
+    let other_night = character.get("otherNight").and_then(Value::as_f64);
+    let first_wake = first_night
+        .filter(|pos| *pos > 0.0)
+        .map(|pos| nearest_wake(pos, night_order, Night::First));
+    let other_wake = other_night
+        .filter(|pos| *pos > 0.0)
+        .map(|pos| nearest_wake(pos, night_order, Night::Other));
+    match (first_wake, other_wake) {
+        (Some(first), Some(other)) if first == other => {
+            out.push_str(&format!("wakes every {} {}\n", first.0, first.1));
+        }
+        (first, other) => {
+            if let Some((relation, id)) = first {
+                out.push_str(&format!("wakes first {relation} {id}\n"));
+            }
+            if let Some((relation, id)) = other {
+                out.push_str(&format!("wakes other {relation} {id}\n"));
+            }
+        }
+    }
+
+    if character.get("setup").and_then(Value::as_bool) == Some(true) {
+        out.push_str("setup\n");
+    }
+    if let Some(Value::Array(special)) = character.get("special") {
+        for special in special.iter().filter_map(Value::as_object) {
+            let field = |key: &str| special.get(key).and_then(Value::as_str).unwrap_or_default();
+            match (field("type"), field("name")) {
+                ("selection", "bag-disabled") => out.push_str("bagdisabled\n"),
+                ("selection", "bag-duplicate") => out.push_str("bagduplicate\n"),
+                ("signal", "grimoire") if field("global") == "demon" => {
+                    out.push_str("grimoire_global_demon\n")
+                }
+                ("signal", "grimoire") => out.push_str("grimoire\n"),
+                ("reveal", "replace-character") => out.push_str("replacereveal\n"),
+                ("signal", "card") => out.push_str(&format!("card {}\n", field("value"))),
+                (kind, name) => diagnostics.warning(
+                    source,
+                    0,
+                    0,
+                    format!(
+                        "Unsupported special {kind} {name} for character {}",
+                        str_field("name")
+                    ),
+                ),
+            }
+        }
+    }
+    if let Some(Value::Array(jinxes)) = character.get("jinxes") {
+        for jinx in jinxes.iter().filter_map(Value::as_object) {
+            let (Some(id), Some(reason)) = (
+                jinx.get("id").and_then(Value::as_str),
+                jinx.get("reason").and_then(Value::as_str),
+            ) else {
+                continue;
+            };
+            let id = id_map.get(id).cloned().unwrap_or_else(|| normalize_id(id));
+            out.push_str(&format!("jinx {id} {}\n", reason.replace('\n', " ")));
+        }
+    }
+
+    for (key, field) in [("attribution", "attribution"), ("flavour", "flavor")] {
+        let lines: Vec<_> = match character.get(field) {
+            Some(Value::String(text)) => text.lines().map(str::to_owned).collect(),
+            Some(Value::Array(lines)) => lines
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_owned)
+                .collect(),
+            _ => vec![],
+        };
+        let lines: Vec<_> = lines
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .collect();
+        if !lines.is_empty() {
+            out.push_str(&format!("\n{key}\n"));
+            for line in lines {
+                out.push_str(line.trim());
+                out.push('\n');
+            }
+        }
+    }
+
+    out
+}
+
+fn count_reminders(reminders: Option<&Value>) -> Vec<(String, usize)> {
+    let mut counts: Vec<(String, usize)> = vec![];
+
+    if let Some(Value::Array(reminders)) = reminders {
+        for reminder in reminders.iter().filter_map(Value::as_str) {
+            if let Some((_, count)) = counts.iter_mut().find(|(other, _)| other == reminder) {
+                *count += 1;
+            } else {
+                counts.push((reminder.to_owned(), 1));
+            }
+        }
+    }
+
+    counts
+}
+
+/// The official character an export's night position follows. Exports count positions in the
+/// official night order from 1, so they are ranked against `night-order.json` rather than compared
+/// with the positions characters are given once loaded.
+fn nearest_wake(pos: f64, night_order: &NightOrder, night: Night) -> (&'static str, String) {
+    let ids = night_order.ids(night);
+    // A character placed at the same position as an official one wakes after it
+    let listed_before = (pos as usize).min(ids.len());
+
+    match listed_before.checked_sub(1) {
+        Some(idx) => ("after", pseudo_id(&ids[idx])),
+        None => (
+            "before",
+            ids.first().map(|id| pseudo_id(id)).unwrap_or_default(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn night_order() -> NightOrder {
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect();
+        NightOrder {
+            first_night: ids(&["DUSK", "poisoner", "washerwoman", "DAWN"]),
+            other_night: ids(&["DUSK", "poisoner", "monk", "empath", "DAWN"]),
+        }
+    }
+
+    fn source(character: Value) -> String {
+        let mut diagnostics = Diagnostics::default();
+        character_source(
+            character.as_object().unwrap(),
+            &HashMap::new(),
+            &night_order(),
+            "test",
+            &mut diagnostics,
+        )
+    }
+
+    #[test]
+    fn wakes_between_official_characters() {
+        let out = source(json!({
+            "id": "alchemist",
+            "name": "Alchemist",
+            "team": "townsfolk",
+            "ability": "Each night, learn something.",
+            "firstNight": 2.5,
+            "otherNight": 3.5,
+        }));
+        assert!(out.contains("wakes first after poisoner\n"), "{out}");
+        assert!(out.contains("wakes other after monk\n"), "{out}");
+
+        let out = source(json!({
+            "id": "alchemist",
+            "name": "Alchemist",
+            "team": "townsfolk",
+            "ability": "Each night, learn something.",
+            "firstNight": 0.5,
+            "otherNight": 0.5,
+        }));
+        assert!(out.contains("wakes every before dusk\n"), "{out}");
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+
+use crate::{character::Character, diagnostics::Diagnostics, script::Script};
+
+impl Script {
+    /// Warns about jinxes that can never show up, looking up unknown ids in `character_list`
+    pub fn check_jinxes(
+        &self,
+        character_list: &HashMap<String, Character>,
+        diagnostics: &mut Diagnostics,
+    ) {
+        for character in &self.characters {
+            let line = self
+                .lines
+                .get(unpatched(&character.id))
+                .copied()
+                .unwrap_or_default();
+            let column = if line == 0 { 0 } else { 1 };
+
+            for jinx in &character.jinxes {
+                if jinx.id == character.id {
+                    continue;
+                }
+                let id = unpatched(&jinx.id);
+                if !character_list.contains_key(id) {
+                    diagnostics.warning(
+                        &self.source,
+                        line,
+                        column,
+                        format!(
+                            "{} has a jinx with unknown character {}",
+                            character.name, jinx.id
+                        ),
+                    );
+                    continue;
+                }
+                if self.characters.iter().any(|other| other.id == jinx.id) {
+                    continue;
+                }
+                if let Some(other) = self
+                    .characters
+                    .iter()
+                    .find(|other| unpatched(&other.id) == id)
+                {
+                    diagnostics.warning(
+                        &self.source,
+                        line,
+                        column,
+                        format!(
+                            "The jinx of {} with {} names {}, but the script has {}",
+                            character.name, other.name, jinx.id, other.id
+                        ),
+                    );
+                }
+            }
+        }
+
+        for pair in self.jinx_pairs() {
+            if let (Some(first), Some(second)) = (pair.first_reason, pair.second_reason) {
+                if first != second {
+                    diagnostics.warning(
+                        &self.source,
+                        0,
+                        0,
+                        format!(
+                            "{} and {} have a different jinx with each other, both are shown",
+                            pair.first.name, pair.second.name
+                        ),
+                    );
+                }
+            }
+            // Official jinxes live on one side only, but homebrew authors can add both
+            if pair.is_one_sided() && !pair.first.official && !pair.second.official {
+                let line = self
+                    .lines
+                    .get(unpatched(&pair.second.id))
+                    .copied()
+                    .unwrap_or_default();
+                diagnostics.warning(
+                    &self.source,
+                    line,
+                    if line == 0 { 0 } else { 1 },
+                    format!(
+                        "{} has a jinx with {}, but {} has none with {}",
+                        pair.first.name, pair.second.name, pair.second.name, pair.first.name
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// The id of a character before `apply_patches` renamed it
+pub fn unpatched(id: &str) -> &str {
+    id.strip_prefix("patched_").unwrap_or(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::character::{
+        tests::{character, character_list},
+        Jinx,
+    };
+
+    fn jinxed(mut character: Character, jinxes: &[(&str, &str)]) -> Character {
+        character.jinxes = jinxes
+            .iter()
+            .map(|(id, reason)| Jinx {
+                id: id.to_string(),
+                reason: reason.to_string(),
+            })
+            .collect();
+        character
+    }
+
+    fn official(mut character: Character) -> Character {
+        character.official = true;
+        character
+    }
+
+    fn characters() -> HashMap<String, Character> {
+        character_list(vec![
+            official(jinxed(
+                character("spy", "Spy", "minion", ""),
+                &[
+                    ("magician", "Spy and Magician"),
+                    ("alchemist", "Spy and Alchemist"),
+                ],
+            )),
+            official(character("magician", "Magician", "townsfolk", "")),
+            official(jinxed(
+                character("alchemist", "Alchemist", "townsfolk", ""),
+                &[("spy", "Alchemist and Spy")],
+            )),
+            jinxed(
+                character("brew", "Brew", "townsfolk", ""),
+                &[("other", "Brew and Other"), ("nobody", "Brew and Nobody")],
+            ),
+            character("other", "Other", "outsider", ""),
+        ])
+    }
+
+    fn script(ids: &[&str]) -> (Script, Diagnostics) {
+        let characters = characters();
+        let text = format!("Test\nTester\n\n{}", ids.join("\n"));
+        let mut diagnostics = Diagnostics::default();
+        let script = Script::parse("test", &text, &characters, &mut diagnostics).unwrap();
+        script.check_jinxes(&characters, &mut diagnostics);
+        (script, diagnostics)
+    }
+
+    fn messages(diagnostics: &Diagnostics) -> Vec<&str> {
+        diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn pairs_list_both_sides() {
+        let (script, _) = script(&["magician", "alchemist", "spy"]);
+        let pairs = script.jinx_pairs();
+        assert_eq!(pairs.len(), 2);
+
+        assert_eq!(pairs[0].first.id, "alchemist");
+        assert_eq!(pairs[0].second.id, "spy");
+        assert_eq!(pairs[0].first_reason, Some("Alchemist and Spy"));
+        assert_eq!(pairs[0].second_reason, Some("Spy and Alchemist"));
+        assert_eq!(
+            pairs[0].reasons(),
+            vec!["Alchemist and Spy", "Spy and Alchemist"]
+        );
+
+        assert_eq!(pairs[1].first.id, "spy");
+        assert_eq!(pairs[1].second.id, "magician");
+        assert!(pairs[1].is_one_sided());
+        assert_eq!(pairs[1].reasons(), vec!["Spy and Magician"]);
+    }
+
+    #[test]
+    fn warns_about_differing_jinxes() {
+        let (_, diagnostics) = script(&["alchemist", "spy"]);
+        assert_eq!(
+            messages(&diagnostics),
+            vec!["Alchemist and Spy have a different jinx with each other, both are shown"]
+        );
+    }
+
+    #[test]
+    fn one_sided_official_jinxes_are_fine() {
+        let (_, diagnostics) = script(&["magician", "spy"]);
+        assert!(messages(&diagnostics).is_empty());
+    }
+
+    #[test]
+    fn warns_about_one_sided_homebrew_jinxes() {
+        let (_, diagnostics) = script(&["brew", "other"]);
+        let messages = messages(&diagnostics);
+        assert!(messages.contains(&"Brew has a jinx with Other, but Other has none with Brew"));
+        assert!(messages.contains(&"Brew has a jinx with unknown character nobody"));
+        let one_sided = diagnostics
+            .iter()
+            .find(|diagnostic| diagnostic.message.starts_with("Brew has a jinx with Other"))
+            .unwrap();
+        assert_eq!(one_sided.line, 5);
+    }
+
+    #[test]
+    fn unpatched_strips_the_prefix() {
+        assert_eq!(unpatched("patched_spy"), "spy");
+        assert_eq!(unpatched("spy"), "spy");
+    }
+}
@@ -0,0 +1,37 @@
+mod almanac;
+mod almanac_text;
+mod bag;
+mod build;
+mod change_report;
+mod character;
+mod config;
+mod database;
+mod diagnostics;
+mod export;
+mod import;
+mod jinx;
+mod lint;
+mod markup;
+mod night_order;
+mod night_sheet;
+mod patch;
+mod script;
+mod setup;
+mod sheet;
+mod special_characters;
+mod validate;
+
+pub use bag::Bag;
+pub use build::{build, Output};
+pub use character::{Character, Jinx, Team};
+pub use config::{Config, CONFIG_PATH};
+pub use database::CharacterDatabase;
+pub use diagnostics::{Diagnostic, Diagnostics, Severity};
+pub use import::{import, new_character};
+pub use lint::Lint;
+pub use markup::Markup;
+pub use patch::Patch;
+pub use script::{Script, ScriptCache};
+pub use sheet::Paper;
+pub use special_characters::{special_characters, SpecialCharacters};
+pub use validate::{read_schema, validate};
@@ -0,0 +1,280 @@
+use std::{fmt, str::FromStr};
+
+use crate::{character::Team, diagnostics::Diagnostics, script::Script, setup::BASE_DISTRIBUTIONS};
+
+/// The most entries the official app accepts in a script json, `_meta` included
+pub const MAX_ITEMS: usize = 151;
+
+/// Townsfolk needed for a 15 player game with three bluffs left over
+pub const MIN_TOWNSFOLK: usize = 13;
+
+/// Words in an ability that suggest it can give good players false information
+static MISINFORMATION: [&str; 8] = [
+    "poison",
+    "drunk",
+    "register",
+    "false",
+    "incorrect",
+    "arbitrary",
+    "might be wrong",
+    "\"mad\"",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lint {
+    TooFewTownsfolk,
+    NoDemon,
+    TooManyItems,
+    DuplicateCharacter,
+    Travellers,
+    Fabled,
+    NoMisinformation,
+    TooFewOutsiders,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 8] = [
+        Lint::TooFewTownsfolk,
+        Lint::NoDemon,
+        Lint::TooManyItems,
+        Lint::DuplicateCharacter,
+        Lint::Travellers,
+        Lint::Fabled,
+        Lint::NoMisinformation,
+        Lint::TooFewOutsiders,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::TooFewTownsfolk => "too-few-townsfolk",
+            Lint::NoDemon => "no-demon",
+            Lint::TooManyItems => "too-many-items",
+            Lint::DuplicateCharacter => "duplicate-character",
+            Lint::Travellers => "travellers",
+            Lint::Fabled => "fabled",
+            Lint::NoMisinformation => "no-misinformation",
+            Lint::TooFewOutsiders => "too-few-outsiders",
+        }
+    }
+}
+
+impl FromStr for Lint {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Lint::ALL
+            .into_iter()
+            .find(|lint| lint.name() == value)
+            .ok_or_else(|| format!("Unknown lint {value}"))
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Script {
+    /// Runs the lints on a prepared script, skipping those allowed by the script or `allowed`
+    pub fn lint(&self, allowed: &[Lint], diagnostics: &mut Diagnostics) {
+        let mut warn = |lint: Lint, line: usize, message: String| {
+            if !allowed.contains(&lint) && !self.allowed_lints.contains(&lint) {
+                diagnostics.warning(
+                    &self.source,
+                    line,
+                    if line == 0 { 0 } else { 1 },
+                    format!("{message} [{lint}]"),
+                );
+            }
+        };
+        let count = |team: Team| {
+            self.characters
+                .iter()
+                .filter(|character| character.team == team)
+                .count()
+        };
+
+        let townsfolk = count(Team::Townsfolk);
+        if townsfolk < MIN_TOWNSFOLK {
+            warn(
+                Lint::TooFewTownsfolk,
+                0,
+                format!("Script has {townsfolk} Townsfolk, 15 players need {MIN_TOWNSFOLK}"),
+            );
+        }
+
+        if count(Team::Demon) == 0 {
+            warn(Lint::NoDemon, 0, String::from("Script has no Demon"));
+        }
+
+        let items = self.characters.len() + 1;
+        if items > MAX_ITEMS {
+            warn(
+                Lint::TooManyItems,
+                0,
+                format!("Script has {items} items, the app allows {MAX_ITEMS}"),
+            );
+        }
+
+        for (id, line) in &self.duplicates {
+            warn(
+                Lint::DuplicateCharacter,
+                *line,
+                format!("Character {id} is listed more than once"),
+            );
+        }
+
+        // Characters added for requirements or jinxes are there on purpose
+        for character in &self.characters {
+            let id = character
+                .id
+                .strip_prefix("patched_")
+                .unwrap_or(&character.id);
+            let Some(line) = self.lines.get(id) else {
+                continue;
+            };
+            let lint = match character.team {
+                Team::Traveller => Lint::Travellers,
+                Team::Fabled => Lint::Fabled,
+                _ => continue,
+            };
+            warn(
+                lint,
+                *line,
+                format!(
+                    "Script includes the {} {}",
+                    character.team.to_str(),
+                    character.name
+                ),
+            );
+        }
+
+        let has_misinformation = self.characters.iter().any(|character| {
+            let ability = character.ability.to_lowercase();
+            MISINFORMATION.iter().any(|word| ability.contains(word))
+        });
+        if count(Team::Demon) > 0 && !has_misinformation {
+            warn(
+                Lint::NoMisinformation,
+                0,
+                String::from("Nothing on the script can give good players false information"),
+            );
+        }
+
+        // Only Outsider-modifying setup characters can ask for more than the base table
+        let outsiders = count(Team::Outsider);
+        let needed = self
+            .setup_modifiers
+            .iter()
+            .flat_map(|modifier| {
+                modifier
+                    .changes
+                    .iter()
+                    .filter(|change| change.team == Team::Outsider && change.character.is_none())
+                    .map(move |change| (modifier, change))
+            })
+            .flat_map(|(modifier, change)| {
+                BASE_DISTRIBUTIONS.iter().flat_map(move |(_, base)| {
+                    change
+                        .options
+                        .iter()
+                        .map(move |option| (base.outsiders + change.delta(*option, base), modifier))
+                })
+            })
+            .max_by_key(|(needed, _)| *needed);
+        if let Some((needed, modifier)) = needed {
+            let needed = needed.max(0) as usize;
+            if outsiders < needed {
+                let name = self
+                    .characters
+                    .iter()
+                    .find(|character| character.id == modifier.character)
+                    .map_or(modifier.character.as_str(), |character| &character.name);
+                warn(
+                    Lint::TooFewOutsiders,
+                    0,
+                    format!(
+                        "The {name} can call for {needed} Outsiders, but the script has {outsiders}"
+                    ),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::character::tests::{character, character_list};
+
+    fn lint(ids: &[&str], allowed: &[Lint]) -> Vec<String> {
+        let characters = character_list(vec![
+            character("baron", "Baron", "minion", "[+2 Outsiders]"),
+            character("imp", "Imp", "demon", "Poisoned players are drunk."),
+            character("chef", "Chef", "townsfolk", ""),
+            character("butler", "Butler", "outsider", ""),
+            character("drunk", "Drunk", "outsider", ""),
+            character("saint", "Saint", "outsider", ""),
+            character("recluse", "Recluse", "outsider", ""),
+        ]);
+        let text = format!("Test\nTester\n\n{}", ids.join("\n"));
+        let mut diagnostics = Diagnostics::default();
+        let mut script = Script::parse("test", &text, &characters, &mut diagnostics).unwrap();
+        script.resolve_setup(&characters);
+        script.lint(allowed, &mut diagnostics);
+        diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.clone())
+            .collect()
+    }
+
+    fn fired(messages: &[String], lint: Lint) -> bool {
+        let tag = format!("[{lint}]");
+        messages.iter().any(|message| message.ends_with(&tag))
+    }
+
+    #[test]
+    fn too_few_outsiders_needs_a_modifier() {
+        let messages = lint(&["chef", "butler", "imp"], &[]);
+        assert!(!fired(&messages, Lint::TooFewOutsiders));
+
+        let messages = lint(&["chef", "butler", "drunk", "saint", "baron", "imp"], &[]);
+        assert!(messages.iter().any(|message| message
+            == "The Baron can call for 4 Outsiders, but the script has 3 [too-few-outsiders]"));
+
+        let messages = lint(
+            &[
+                "chef", "butler", "drunk", "saint", "recluse", "baron", "imp",
+            ],
+            &[],
+        );
+        assert!(!fired(&messages, Lint::TooFewOutsiders));
+    }
+
+    #[test]
+    fn reports_team_composition() {
+        let messages = lint(&["chef", "chef"], &[]);
+        assert!(fired(&messages, Lint::TooFewTownsfolk));
+        assert!(fired(&messages, Lint::NoDemon));
+        assert!(fired(&messages, Lint::DuplicateCharacter));
+        assert!(!fired(&messages, Lint::NoMisinformation));
+    }
+
+    #[test]
+    fn allowed_lints_are_skipped() {
+        let messages = lint(&["chef"], &[Lint::NoDemon, Lint::TooFewTownsfolk]);
+        assert!(messages.is_empty());
+
+        let messages = lint(&["allow no-demon", "chef"], &[Lint::TooFewTownsfolk]);
+        assert!(messages.is_empty());
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for lint in Lint::ALL {
+            assert_eq!(lint.name().parse::<Lint>(), Ok(lint));
+        }
+        assert!("nonsense".parse::<Lint>().is_err());
+    }
+}
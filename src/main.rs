@@ -1,119 +1,583 @@
-mod almanac;
-mod character;
-mod patch;
-mod script;
-mod special_characters;
-
 use std::{
-    collections::HashMap,
-    env::args,
-    fs::{create_dir_all, File},
-    io::Read,
+    fs::File,
+    io::{self, stdout, Write},
     path::Path,
+    process::exit,
 };
 
-use almanac::write_index;
-use character::Character;
-use patch::read_patches;
-use script::Script;
-use special_characters::{special_characters, SpecialCharacters};
+use clap::{Parser, Subcommand, ValueEnum};
+use rand::{random, rngs::StdRng, SeedableRng};
+use script_gen::{
+    build, import, new_character, read_schema, validate, Bag, Character, CharacterDatabase, Config,
+    Diagnostics, Markup, Output, Script, ScriptCache, Team, CONFIG_PATH,
+};
 
-fn main() {
-    let mut image_list_str = String::new();
-    File::open("official-images")
-        .expect("Official image list not found")
-        .read_to_string(&mut image_list_str)
-        .expect("Failed to read official image list file");
-    let mut image_list = HashMap::new();
-    for image in image_list_str.lines().filter(|str| !str.is_empty()) {
-        let mut iter = image.split(' ');
-        let id = iter.next().unwrap().to_owned();
-        let images: Vec<String> = iter
-            .map(|image| format!("https://botc.app/assets/{image}.webp"))
-            .collect();
+/// Generates almanacs, character sheets and app json for Blood on the Clocktower scripts
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Cli {
+    /// Project config file
+    #[arg(long, global = true, default_value = CONFIG_PATH)]
+    config: String,
+    /// Official character json, overrides the config
+    #[arg(long, global = true)]
+    characters: Option<String>,
+    /// Official character image list, overrides the config
+    #[arg(long, global = true)]
+    official_images: Option<String>,
+    /// Official night order json, overrides the config
+    #[arg(long, global = true)]
+    night_order: Option<String>,
+    /// Directory of `.char` files, overrides the config
+    #[arg(long, global = true)]
+    character_dir: Option<String>,
+    /// Directory of bootlegger patches, overrides the config
+    #[arg(long, global = true)]
+    bootlegger_dir: Option<String>,
+    /// Directory of script sources, overrides the config
+    #[arg(long, global = true)]
+    source_dir: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Generate the site for the given scripts, or every script in the source directory
+    ///
+    /// The index is only written for named scripts when asked for, and then lists every script
+    Build {
+        /// Output directory, overrides the config
+        #[arg(short, long)]
+        out: Option<String>,
+        /// Outputs to generate, all but the json for other tools and the text almanacs by default
+        #[arg(short, long, value_enum, value_delimiter = ',')]
+        format: Vec<Format>,
+        /// Script source files or names in the source directory
+        scripts: Vec<String>,
+    },
+    /// Check scripts for questionable character choices
+    Lint {
+        /// Script source files or names in the source directory
+        scripts: Vec<String>,
+    },
+    /// List the jinxes between the characters of scripts, both ways round
+    Jinxes {
+        /// Script source files or names in the source directory
+        scripts: Vec<String>,
+    },
+    /// Compare the official night positions in the character json with the night order file
+    NightOrder,
+    /// Check app json scripts against the schema
+    Validate {
+        /// Schema to validate against, overrides the config
+        #[arg(long)]
+        schema: Option<String>,
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+    /// Create script and character sources from app json scripts
+    Import {
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+    /// Print the almanac of a script or one of its characters as Markdown
+    Almanac {
+        /// Script source file or name in the source directory
+        script: String,
+        /// Only print the page of this character
+        #[arg(long)]
+        character: Option<String>,
+        /// Print plain text instead of Markdown
+        #[arg(long)]
+        plain: bool,
+    },
+    /// Print the app json for a script
+    Export {
+        /// Script source file or name in the source directory
+        script: String,
+        /// Tool to write the json for
+        #[arg(short, long, value_enum, default_value_t = JsonFormat::Official)]
+        format: JsonFormat,
+        /// File to write to instead of stdout
+        #[arg(short, long)]
+        out: Option<String>,
+    },
+    /// Create a character source file to fill in
+    NewCharacter {
+        /// Character id, also the file name
+        id: String,
+        /// Display name, defaults to the id
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(long, value_parser = parse_team)]
+        team: Team,
+        /// Subdirectory of the character directory, usually the script the character is for
+        #[arg(long)]
+        dir: Option<String>,
+    },
+    /// Draw the characters for a game of a script
+    Bag {
+        /// Script source file or name in the source directory
+        script: String,
+        /// Number of players, not counting travellers
+        players: usize,
+        /// Number of travellers joining
+        #[arg(long, default_value_t = 0)]
+        travellers: usize,
+        /// Seed to repeat an earlier draw
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// List known scripts or characters
+    List {
+        #[arg(value_enum, default_value_t = ListKind::Scripts)]
+        kind: ListKind,
+        /// Only list characters on this team
+        #[arg(long, value_parser = parse_team)]
+        team: Option<Team>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Json,
+    Almanac,
+    Sheet,
+    NightSheet,
+    Setup,
+    Index,
+    ClocktowerOnline,
+    Bloodstar,
+    Markdown,
+    Text,
+    Changes,
+}
 
-        image_list.insert(id, images);
+impl From<Format> for Output {
+    fn from(format: Format) -> Output {
+        match format {
+            Format::Json => Output::Json,
+            Format::Almanac => Output::Almanac,
+            Format::Sheet => Output::Sheet,
+            Format::NightSheet => Output::NightSheet,
+            Format::Setup => Output::Setup,
+            Format::Index => Output::Index,
+            Format::ClocktowerOnline => Output::ClocktowerOnline,
+            Format::Bloodstar => Output::Bloodstar,
+            Format::Markdown => Output::Markdown,
+            Format::Text => Output::Text,
+            Format::Changes => Output::Changes,
+        }
     }
-    let mut character_list_str = String::new();
-    File::open("characters.json")
-        .expect("Character list not found")
-        .read_to_string(&mut character_list_str)
-        .expect("Failed to read character list file");
-    let mut character_list = HashMap::new();
-    for mut character in serde_json::from_str::<Vec<Character>>(&character_list_str)
-        .expect("Failed to parse character list json")
-    {
-        character.official = true;
-        if let Some(image) = image_list.get(&character.id) {
-            character.image = image.clone();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum JsonFormat {
+    Official,
+    ClocktowerOnline,
+    Bloodstar,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ListKind {
+    Scripts,
+    Characters,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let mut diagnostics = Diagnostics::default();
+    run(cli, &mut diagnostics);
+    diagnostics.report();
+    if diagnostics.has_errors() {
+        exit(1);
+    }
+}
+
+fn run(cli: Cli, diagnostics: &mut Diagnostics) {
+    let Some(mut config) = Config::load(&cli.config, diagnostics) else {
+        return;
+    };
+    let overrides = [
+        (cli.characters, &mut config.input.characters),
+        (cli.official_images, &mut config.input.official_images),
+        (cli.night_order, &mut config.input.night_order),
+        (cli.character_dir, &mut config.input.character_dir),
+        (cli.bootlegger_dir, &mut config.input.bootlegger_dir),
+        (cli.source_dir, &mut config.input.source_dir),
+    ];
+    for (value, field) in overrides {
+        if let Some(value) = value {
+            *field = value;
         }
-        character_list.insert(character.id.clone(), character);
     }
 
-    let SpecialCharacters {
-        dusk,
-        minions,
-        demon,
-        dawn,
-    } = special_characters();
-    let first_night_special = [&dusk, &minions, &demon, &dawn];
-    let other_night_special = [&dusk, &dawn];
+    let load = |diagnostics: &mut Diagnostics| CharacterDatabase::load(&config, diagnostics);
+
+    match cli.command {
+        Command::Validate { schema, files } => {
+            let schema_path = schema.as_ref().unwrap_or(&config.input.schema);
+            if let Some(schema) = read_schema(schema_path, diagnostics) {
+                for source in files {
+                    validate(&source, &schema, diagnostics);
+                }
+            }
+        }
+        Command::Build {
+            out,
+            format,
+            scripts,
+        } => {
+            let Some(database) = load(diagnostics) else {
+                return;
+            };
+            // A subset build would overwrite the site index with only its own scripts
+            let outputs: Vec<Output> = if format.is_empty() {
+                Output::DEFAULT
+                    .into_iter()
+                    .filter(|output| *output != Output::Index || scripts.is_empty())
+                    .collect()
+            } else {
+                format.into_iter().map(Output::from).collect()
+            };
+            let out_dir = out.unwrap_or_else(|| config.output.dir.clone());
+            let allowed = config.lints.allowed(&cli.config, diagnostics);
+            build(
+                &config,
+                &database,
+                &scripts,
+                &outputs,
+                &out_dir,
+                &allowed,
+                diagnostics,
+            );
+        }
+        Command::Lint { scripts } => {
+            let Some(database) = load(diagnostics) else {
+                return;
+            };
+            let allowed = config.lints.allowed(&cli.config, diagnostics);
+            let mut cache = ScriptCache::default();
+            for source in config.input.script_list(&scripts, diagnostics) {
+                if let Some(script) = database.prepare_script(&source, &mut cache, diagnostics) {
+                    script.lint(&allowed, diagnostics);
+                    script.check_jinxes(&database.characters, diagnostics);
+                }
+            }
+        }
+        Command::Jinxes { scripts } => {
+            let Some(database) = load(diagnostics) else {
+                return;
+            };
+            let mut cache = ScriptCache::default();
+            for source in config.input.script_list(&scripts, diagnostics) {
+                if let Some(script) = database.prepare_script(&source, &mut cache, diagnostics) {
+                    script.check_jinxes(&database.characters, diagnostics);
+                    print_jinxes(&script);
+                }
+            }
+        }
+        Command::NightOrder => {
+            if let Some(database) = load(diagnostics) {
+                print_night_order_mismatches(&config, &database);
+            }
+        }
+        Command::Import { files } => {
+            let Some(database) = load(diagnostics) else {
+                return;
+            };
+            for source in files {
+                import(
+                    &source,
+                    &database.characters,
+                    &database.night_order,
+                    &config,
+                    diagnostics,
+                );
+            }
+        }
+        Command::Export {
+            script,
+            format,
+            out,
+        } => {
+            let Some(database) = load(diagnostics) else {
+                return;
+            };
+            let source = config.input.script_path(&script);
+            let Some(script) =
+                database.prepare_script(&source, &mut ScriptCache::default(), diagnostics)
+            else {
+                return;
+            };
+            let file_name = file_name(&source);
+            let write = |mut writer: Box<dyn Write>| match format {
+                JsonFormat::Official => script.write_json(&mut writer, &file_name, &config),
+                JsonFormat::ClocktowerOnline => {
+                    script.write_clocktower_online(&mut writer, &file_name, &config)
+                }
+                JsonFormat::Bloodstar => script.write_bloodstar(&mut writer, &file_name, &config),
+            };
+            let result = match &out {
+                Some(out) => File::create(out).and_then(|writer| write(Box::new(writer))),
+                None => write(Box::new(stdout())),
+            };
+            if let Err(err) = result.or_else(closed_stdout) {
+                diagnostics.error(
+                    out.as_deref().unwrap_or(&source),
+                    0,
+                    0,
+                    format!("Failed to write script json: {err}"),
+                );
+            }
+        }
+        Command::Almanac {
+            script,
+            character,
+            plain,
+        } => {
+            let Some(database) = load(diagnostics) else {
+                return;
+            };
+            let source = config.input.script_path(&script);
+            let Some(script) =
+                database.prepare_script(&source, &mut ScriptCache::default(), diagnostics)
+            else {
+                return;
+            };
+            let markup = if plain {
+                Markup::Plain
+            } else {
+                Markup::Markdown
+            };
+            let result =
+                match character {
+                    Some(id) => match script.characters.iter().find(|character| {
+                        character.id == id || character.id == format!("patched_{id}")
+                    }) {
+                        Some(character) => {
+                            script.write_character_text(&mut stdout(), character, markup)
+                        }
+                        None => {
+                            diagnostics.error(&source, 0, 0, format!("{id} is not on the script"));
+                            Ok(())
+                        }
+                    },
+                    None => script.write_almanac_text(&mut stdout(), markup),
+                };
+            if let Err(err) = result.or_else(closed_stdout) {
+                diagnostics.error(&source, 0, 0, format!("Failed to print almanac: {err}"));
+            }
+        }
+        Command::Bag {
+            script,
+            players,
+            travellers,
+            seed,
+        } => {
+            let Some(database) = load(diagnostics) else {
+                return;
+            };
+            let source = config.input.script_path(&script);
+            let Some(script) =
+                database.prepare_script(&source, &mut ScriptCache::default(), diagnostics)
+            else {
+                return;
+            };
+            let seed = seed.unwrap_or_else(random);
+            let bag = match script.draw_bag(players, travellers, &mut StdRng::seed_from_u64(seed)) {
+                Ok(bag) => bag,
+                Err(err) => {
+                    diagnostics.error(&source, 0, 0, err);
+                    return;
+                }
+            };
+            print_bag(&script, &bag, seed);
+        }
+        Command::NewCharacter {
+            id,
+            name,
+            team,
+            dir,
+        } => {
+            let Some(database) = load(diagnostics) else {
+                return;
+            };
+            new_character(
+                &id,
+                name.as_deref().unwrap_or(&id),
+                team,
+                dir.as_deref(),
+                &database.characters,
+                &config,
+                diagnostics,
+            );
+        }
+        Command::List { kind, team } => {
+            let Some(database) = load(diagnostics) else {
+                return;
+            };
+            match kind {
+                ListKind::Scripts => {
+                    let mut cache = ScriptCache::default();
+                    for source in config.input.script_sources(diagnostics) {
+                        if let Some(script) = Script::from_source(
+                            &source,
+                            &database.characters,
+                            &mut cache,
+                            diagnostics,
+                        ) {
+                            println!("{}\t{}\t{}", file_name(&source), script.name, script.author);
+                        }
+                    }
+                }
+                ListKind::Characters => {
+                    let mut characters: Vec<_> = database
+                        .characters
+                        .values()
+                        .filter(|character| {
+                            team.as_ref().is_none_or(|team| character.team == *team)
+                        })
+                        .collect();
+                    characters.sort_by(|a, b| a.team.cmp(&b.team).then_with(|| a.id.cmp(&b.id)));
+                    for character in characters {
+                        println!(
+                            "{}\t{}\t{}\t{}",
+                            character.id,
+                            character.name,
+                            character.team.to_str(),
+                            if character.official {
+                                "official"
+                            } else {
+                                "homebrew"
+                            }
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
 
-    let patches = read_patches();
+fn print_bag(script: &Script, bag: &Bag, seed: u64) {
+    let names = |characters: &[&Character]| {
+        characters
+            .iter()
+            .map(|character| character.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let distribution = bag.distribution;
 
-    load_dir(
-        &Path::new("script-gen").join("characters"),
-        &mut character_list,
+    println!(
+        "{} with {} players ({}/{}/{}/{}), seed {seed}",
+        script.name,
+        bag.players,
+        distribution.townsfolk,
+        distribution.outsiders,
+        distribution.minions,
+        distribution.demons
     );
-
-    let mut args = args().skip(1);
-    let out_dir = args.next().expect("No out dir provided");
-    create_dir_all(&out_dir).expect("Failed to create out dir");
-
-    let mut index_entries = vec![];
-
-    for source in args {
-        let file_name = Path::new(&source).file_name().unwrap().to_str().unwrap();
-        let mut script = Script::from_source(&source, &character_list);
-        let mut json_writer =
-            File::create(Path::new(&out_dir).join(format!("{file_name}.official.json",)))
-                .unwrap_or_else(|_| panic!("Failed to create script file for script {source}"));
-        let mut html_writer = File::create(Path::new(&out_dir).join(format!("{file_name}.html",)))
-            .unwrap_or_else(|_| panic!("Failed to create almanac file for script {source}"));
-
-        script.resolve_required(&character_list);
-        script.apply_patches(&patches, &image_list);
-        script.write_json(&mut json_writer, file_name);
-        script.write_html(&mut html_writer, &first_night_special, &other_night_special);
-        index_entries.push((
-            Path::new(&source)
-                .file_name()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string(),
-            script.name,
-        ));
+    for team in [Team::Townsfolk, Team::Outsider, Team::Minion, Team::Demon] {
+        let characters: Vec<_> = bag
+            .in_play
+            .iter()
+            .copied()
+            .filter(|character| character.team == team)
+            .collect();
+        if !characters.is_empty() {
+            println!("{}: {}", team.to_str(), names(&characters));
+        }
     }
+    if bag.tokens.len() != bag.in_play.len()
+        || bag
+            .tokens
+            .iter()
+            .zip(&bag.in_play)
+            .any(|(token, character)| token.id != character.id)
+    {
+        println!("bag: {}", names(&bag.tokens));
+    }
+    if !bag.travellers.is_empty() {
+        println!("travellers: {}", names(&bag.travellers));
+    }
+    println!("bluffs: {}", names(&bag.bluffs));
+}
 
-    let mut index_writer =
-        File::create(Path::new(&out_dir).join("index.html")).expect("Failed to create index file");
-    write_index(&mut index_writer, &index_entries);
+fn print_jinxes(script: &Script) {
+    println!("{}", script.name);
+    for pair in script.jinx_pairs() {
+        for (from, to, reason) in [
+            (pair.first, pair.second, pair.first_reason),
+            (pair.second, pair.first, pair.second_reason),
+        ] {
+            println!(
+                "  {} -> {}: {}",
+                from.name,
+                to.name,
+                reason.unwrap_or("(one-sided)")
+            );
+        }
+    }
 }
 
-fn load_dir(path: &Path, character_list: &mut HashMap<String, Character>) {
-    if let Ok(dir) = path.read_dir() {
-        for character_entry in dir.flatten() {
-            if character_entry.file_type().is_ok_and(|f| f.is_dir()) {
-                load_dir(&character_entry.path(), character_list);
-            } else if character_entry
-                .path()
-                .extension()
-                .is_some_and(|ext| ext == "char")
-            {
-                let character = Character::from_source(&character_entry.path(), character_list);
-                character_list.insert(character.id.clone(), character);
-            }
+fn print_night_order_mismatches(config: &Config, database: &CharacterDatabase) {
+    let characters = &config.input.characters;
+    let night_order = &config.input.night_order;
+    if database.night_order_mismatches.is_empty() {
+        println!("{characters} and {night_order} agree");
+    }
+
+    for mismatch in &database.night_order_mismatches {
+        let id = &mismatch.id;
+        let night = mismatch.night;
+        match (mismatch.characters, mismatch.night_order) {
+            (Some(listed), Some(ordered)) => println!(
+                "{id}: {listed} on the {night} in {characters}, {ordered} in {night_order}"
+            ),
+            (Some(listed), None) => println!(
+                "{id}: {listed} on the {night} in {characters}, not in {night_order}{}",
+                match database.characters.get(id) {
+                    Some(character) if character.team == Team::Fabled => ", keeping it",
+                    _ => "",
+                }
+            ),
+            (None, Some(ordered)) if database.characters.contains_key(id) => println!(
+                "{id}: not waking on the {night} in {characters}, {ordered} in {night_order}"
+            ),
+            (None, _) => println!("{id}: in {night_order} but not in {characters}"),
         }
     }
 }
+
+fn parse_team(value: &str) -> Result<Team, String> {
+    let mut chars = value.chars();
+    let capitalized = chars
+        .next()
+        .map(|first| {
+            first
+                .to_uppercase()
+                .chain(chars.flat_map(char::to_lowercase))
+        })
+        .into_iter()
+        .flatten()
+        .collect::<String>();
+    capitalized.parse()
+}
+
+/// Piping into something like `head` is not a failure
+fn closed_stdout(err: io::Error) -> io::Result<()> {
+    if err.kind() == io::ErrorKind::BrokenPipe {
+        Ok(())
+    } else {
+        Err(err)
+    }
+}
+
+fn file_name(source: &str) -> String {
+    Path::new(source)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| source.to_owned())
+}
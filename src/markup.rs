@@ -0,0 +1,247 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Markup {
+    Html,
+    Markdown,
+    Plain,
+}
+
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+
+    for char in text.chars() {
+        match char {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(char),
+        }
+    }
+
+    out
+}
+
+/// Escapes the characters Markdown would read as formatting
+pub fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+
+    for char in text.chars() {
+        if matches!(
+            char,
+            '*' | '_' | '[' | ']' | '\\' | '`' | '<' | '>' | '#' | '|' | '~'
+        ) {
+            out.push('\\');
+        }
+        out.push(char);
+    }
+
+    out
+}
+
+pub fn render(text: &str) -> String {
+    render_as(text, Markup::Html)
+}
+
+/// Escapes `text`, rendering `*bold*`, `_italic_` and `[label](url)`, with `\` for a literal
+pub fn render_as(text: &str, markup: Markup) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut prev = None;
+
+    while let Some(char) = rest.chars().next() {
+        match char {
+            '*' | '_' if prev.is_none_or(|prev: char| !prev.is_alphanumeric()) => {
+                if let Some((inner, after)) = emphasis(rest, char) {
+                    let inner = render_as(inner, markup);
+                    out.push_str(&match (markup, char) {
+                        (Markup::Html, '*') => format!("<b>{inner}</b>"),
+                        (Markup::Html, _) => format!("<i>{inner}</i>"),
+                        (Markup::Markdown, '*') => format!("**{inner}**"),
+                        (Markup::Markdown, _) => format!("_{inner}_"),
+                        (Markup::Plain, _) => inner,
+                    });
+                    prev = Some(char);
+                    rest = after;
+                    continue;
+                }
+            }
+            '[' => {
+                if let Some((label, url, after)) = link(rest) {
+                    let label = render_as(label, markup);
+                    out.push_str(&match markup {
+                        Markup::Html => format!("<a href=\"{}\">{label}</a>", escape(url)),
+                        Markup::Markdown => format!("[{label}]({url})"),
+                        Markup::Plain if url.starts_with('#') => label,
+                        Markup::Plain => format!("{label} ({url})"),
+                    });
+                    prev = Some(')');
+                    rest = after;
+                    continue;
+                }
+            }
+            '\\' => {
+                if let Some(next) = rest[1..].chars().next() {
+                    let literal = &rest[1..1 + next.len_utf8()];
+                    out.push_str(&match markup {
+                        Markup::Html => escape(literal),
+                        Markup::Markdown => escape_markdown(literal),
+                        Markup::Plain => literal.to_owned(),
+                    });
+                    prev = Some(next);
+                    rest = &rest[1 + next.len_utf8()..];
+                    continue;
+                }
+            }
+            _ => (),
+        }
+
+        let literal = &rest[..char.len_utf8()];
+        out.push_str(&match markup {
+            Markup::Html => escape(literal),
+            Markup::Markdown => escape_markdown(literal),
+            Markup::Plain => literal.to_owned(),
+        });
+        prev = Some(char);
+        rest = &rest[char.len_utf8()..];
+    }
+
+    out
+}
+
+/// Renders night reminders, which also mark reminder token placement with `:reminder:`
+pub fn render_reminder(text: &str) -> String {
+    text.lines()
+        .map(|line| render(line.trim()))
+        .collect::<Vec<_>>()
+        .join("<br />")
+        .replace(
+            ":reminder:",
+            "<span class=\"reminder-token\">&#9673;</span>",
+        )
+}
+
+/// Splits `*inner*rest` into `inner` and `rest`, as long as the delimiters hug the text
+fn emphasis(text: &str, delimiter: char) -> Option<(&str, &str)> {
+    let body = &text[1..];
+    if body.starts_with(char::is_whitespace) || body.starts_with(delimiter) {
+        return None;
+    }
+
+    for (idx, char) in body.char_indices() {
+        if char != delimiter || idx == 0 {
+            continue;
+        }
+        let inner = &body[..idx];
+        let after = &body[idx + 1..];
+        if !inner.ends_with(char::is_whitespace)
+            && !after.starts_with(|next: char| next.is_alphanumeric())
+        {
+            return Some((inner, after));
+        }
+    }
+
+    None
+}
+
+/// Splits `[label](url)rest` into its parts, only accepting web and in-page links
+fn link(text: &str) -> Option<(&str, &str, &str)> {
+    let label_end = text.find("](")?;
+    let label = &text[1..label_end];
+    let url_start = label_end + 2;
+    let url_end = url_start + text[url_start..].find(')')?;
+    let url = &text[url_start..url_end];
+
+    if label.is_empty()
+        || !["https://", "http://", "#", "/", "mailto:"]
+            .iter()
+            .any(|prefix| url.starts_with(prefix))
+    {
+        return None;
+    }
+
+    Some((label, url, &text[url_end + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, escape_markdown, render, render_as, render_reminder, Markup};
+
+    #[test]
+    fn escapes_html() {
+        assert_eq!(
+            escape(r#"<b>"Tom" & 'Jerry'</b>"#),
+            "&lt;b&gt;&quot;Tom&quot; &amp; &#39;Jerry&#39;&lt;/b&gt;"
+        );
+        assert_eq!(escape("“curly” ’quotes’"), "“curly” ’quotes’");
+    }
+
+    #[test]
+    fn escapes_markdown() {
+        assert_eq!(escape_markdown("*a* _b_ [c]"), r"\*a\* \_b\_ \[c\]");
+        assert_eq!(escape_markdown("1 < 2 # 3"), r"1 \< 2 \# 3");
+    }
+
+    #[test]
+    fn renders_emphasis() {
+        assert_eq!(
+            render("Show the *YOU ARE* token, _then_ sleep"),
+            "Show the <b>YOU ARE</b> token, <i>then</i> sleep"
+        );
+        assert_eq!(render("*_both_*"), "<b><i>both</i></b>");
+    }
+
+    #[test]
+    fn leaves_unmatched_and_mid_word_delimiters() {
+        assert_eq!(render("snake_charmer_token"), "snake_charmer_token");
+        assert_eq!(render("2 * 3 * 4"), "2 * 3 * 4");
+        assert_eq!(render("*open"), "*open");
+    }
+
+    #[test]
+    fn backslash_makes_a_literal() {
+        assert_eq!(render(r"\*not bold\*"), "*not bold*");
+        assert_eq!(render(r"\<"), "&lt;");
+        assert_eq!(
+            render_as(r"\*not bold\*", Markup::Markdown),
+            r"\*not bold\*"
+        );
+    }
+
+    #[test]
+    fn renders_links() {
+        assert_eq!(
+            render("[Wiki](https://wiki.bloodontheclocktower.com/?a=1&b=2)"),
+            "<a href=\"https://wiki.bloodontheclocktower.com/?a=1&amp;b=2\">Wiki</a>"
+        );
+        assert_eq!(
+            render("[Jinxes](#jinxes)"),
+            "<a href=\"#jinxes\">Jinxes</a>"
+        );
+        assert_eq!(
+            render("[x](javascript:alert(1))"),
+            "[x](javascript:alert(1))"
+        );
+    }
+
+    #[test]
+    fn renders_markdown_and_plain() {
+        let text = "*Bold* _italic_ [link](https://example.com) [here](#top) <3";
+        assert_eq!(
+            render_as(text, Markup::Markdown),
+            r"**Bold** _italic_ [link](https://example.com) [here](#top) \<3"
+        );
+        assert_eq!(
+            render_as(text, Markup::Plain),
+            "Bold italic link (https://example.com) here <3"
+        );
+    }
+
+    #[test]
+    fn renders_reminders() {
+        assert_eq!(
+            render_reminder("Wake the *IMP*. :reminder:\n Put them to sleep."),
+            "Wake the <b>IMP</b>. <span class=\"reminder-token\">&#9673;</span><br />Put them to sleep."
+        );
+    }
+}
@@ -0,0 +1,759 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    path::PathBuf,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    character::{Character, Team},
+    database::read_file,
+    diagnostics::Diagnostics,
+};
+
+/// The pseudo-characters of `night-order.json` and the official characters holding their text
+pub const PSEUDO_CHARACTERS: [(&str, &str); 4] = [
+    ("DUSK", "dusk"),
+    ("MINION", "minioninfo"),
+    ("DEMON", "demoninfo"),
+    ("DAWN", "dawn"),
+];
+
+/// The most a homebrew character is placed after the one before it
+const STEP: f32 = 0.1;
+
+/// The official night order, ids of characters and pseudo-characters in waking order
+#[derive(Debug, Deserialize)]
+pub struct NightOrder {
+    #[serde(rename = "firstNight")]
+    pub first_night: Vec<String>,
+    #[serde(rename = "otherNight")]
+    pub other_night: Vec<String>,
+}
+
+/// An official character placed differently by `characters.json` and `night-order.json`, with
+/// `None` for not waking
+#[derive(Debug)]
+pub struct NightOrderMismatch {
+    pub id: String,
+    pub night: Night,
+    pub characters: Option<f32>,
+    pub night_order: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Night {
+    First,
+    Other,
+}
+
+#[derive(Debug, Clone)]
+pub enum Relation {
+    Before(String),
+    After(String),
+    Between(String, String),
+}
+
+/// One night of a `wakes` line
+#[derive(Debug, Clone)]
+pub struct Wake {
+    pub night: Night,
+    pub relation: Relation,
+    pub path: PathBuf,
+    pub line: usize,
+    /// Column of the first id
+    pub column: usize,
+}
+
+impl NightOrder {
+    pub fn read(path: &str, diagnostics: &mut Diagnostics) -> Option<NightOrder> {
+        let night_order_str = read_file(path, diagnostics)?;
+        match serde_json::from_str(&night_order_str) {
+            Ok(night_order) => Some(night_order),
+            Err(err) => {
+                diagnostics.error(path, err.line(), err.column(), err.to_string());
+                None
+            }
+        }
+    }
+
+    pub fn ids(&self, night: Night) -> &[String] {
+        match night {
+            Night::First => &self.first_night,
+            Night::Other => &self.other_night,
+        }
+    }
+
+    /// Position of a character in the list, counting from 1, accepting the official id of a
+    /// pseudo-character as well
+    pub fn position(&self, night: Night, id: &str) -> Option<f32> {
+        self.ids(night)
+            .iter()
+            .position(|listed| listed == id || pseudo_id(listed) == id)
+            .map(|idx| (idx + 1) as f32)
+    }
+
+    /// Moves the official characters to their place in the list, returning where that disagrees
+    /// with their position from `characters.json`
+    pub fn apply(
+        &self,
+        character_list: &mut HashMap<String, Character>,
+    ) -> Vec<NightOrderMismatch> {
+        let mut mismatches = vec![];
+
+        for night in [Night::First, Night::Other] {
+            for character in character_list
+                .values_mut()
+                .filter(|character| character.official)
+            {
+                let listed = Some(night.position(character)).filter(|position| *position > 0.0);
+                let ordered = self.position(night, &character.id);
+                if listed == ordered {
+                    continue;
+                }
+                mismatches.push(NightOrderMismatch {
+                    id: character.id.clone(),
+                    night,
+                    characters: listed,
+                    night_order: ordered,
+                });
+                if ordered.is_some() || character.team != Team::Fabled {
+                    *night.position_mut(character) = ordered.unwrap_or_default();
+                }
+            }
+
+            for id in self.ids(night) {
+                let id = pseudo_id(id);
+                if !character_list.contains_key(&id) {
+                    mismatches.push(NightOrderMismatch {
+                        night_order: self.position(night, &id),
+                        id,
+                        night,
+                        characters: None,
+                    });
+                }
+            }
+        }
+
+        mismatches.sort_by(|a, b| a.id.cmp(&b.id));
+        mismatches
+    }
+}
+
+impl Night {
+    pub fn position(&self, character: &Character) -> f32 {
+        match self {
+            Night::First => character.first_night,
+            Night::Other => character.other_night,
+        }
+    }
+
+    pub fn position_mut<'a>(&self, character: &'a mut Character) -> &'a mut f32 {
+        match self {
+            Night::First => &mut character.first_night,
+            Night::Other => &mut character.other_night,
+        }
+    }
+}
+
+impl fmt::Display for Night {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Night::First => "first night",
+            Night::Other => "other nights",
+        })
+    }
+}
+
+impl Relation {
+    /// Characters that have to wake before and after the character
+    fn anchors(&self) -> (Option<&str>, Option<&str>) {
+        match self {
+            Relation::Before(next) => (None, Some(next)),
+            Relation::After(previous) => (Some(previous), None),
+            Relation::Between(previous, next) => (Some(previous), Some(next)),
+        }
+    }
+}
+
+/// Parses the value of a `wakes` line, `<night> <relation> <id>` or `<night> between <id> <id>`,
+/// returning the column offset of the problem on failure
+pub fn parse_wakes(value: &str) -> Result<(Vec<Night>, Relation, usize), (usize, String)> {
+    let words: Vec<&str> = value.split(' ').collect();
+    let (night, relation, ids) = match words.as_slice() {
+        [night, relation, ids @ ..] if !ids.is_empty() => (*night, *relation, ids),
+        _ => {
+            return Err((
+                0,
+                String::from("Waking pattern must be <night> <relation> <id>"),
+            ))
+        }
+    };
+    let relation_offset = night.len() + 1;
+    let ids_offset = relation_offset + relation.len() + 1;
+
+    let nights = match night {
+        "first" => vec![Night::First],
+        "other" => vec![Night::Other],
+        "every" => vec![Night::First, Night::Other],
+        _ => return Err((0, format!("Invalid night {night}"))),
+    };
+    let relation = match (relation, ids) {
+        ("before", [next]) => Relation::Before(pseudo_id(next)),
+        ("after", [previous]) => Relation::After(pseudo_id(previous)),
+        ("between", [previous, next]) => Relation::Between(pseudo_id(previous), pseudo_id(next)),
+        ("before" | "after", _) => {
+            return Err((ids_offset, format!("Expected one id after {relation}")))
+        }
+        ("between", _) => return Err((ids_offset, String::from("Expected two ids after between"))),
+        _ => return Err((relation_offset, format!("Invalid relation {relation}"))),
+    };
+
+    Ok((nights, relation, ids_offset))
+}
+
+/// Maps the pseudo-characters of `night-order.json` to their official ids
+pub fn pseudo_id(id: &str) -> String {
+    PSEUDO_CHARACTERS
+        .iter()
+        .find(|(pseudo, _)| *pseudo == id)
+        .map_or(id, |(_, official)| official)
+        .to_owned()
+}
+
+/// Gives every character with `wakes` lines its positions, once all characters are loaded
+pub fn resolve_night_order(
+    character_list: &mut HashMap<String, Character>,
+    diagnostics: &mut Diagnostics,
+) {
+    for night in [Night::First, Night::Other] {
+        let positions = night_positions(night, character_list, diagnostics);
+        for character in character_list.values_mut() {
+            if character.wakes.iter().any(|wake| wake.night == night) {
+                *night.position_mut(character) =
+                    positions.get(&character.id).copied().unwrap_or_default();
+            }
+        }
+    }
+}
+
+/// Positions `wakes` lines from a patch give character `id`, placed around the night order
+/// resolved when the characters were loaded. Positions are 0 for not waking, and only the nights
+/// `wakes` has lines for are returned.
+pub fn patched_positions(
+    id: &str,
+    wakes: &[Wake],
+    character_list: &HashMap<String, Character>,
+    diagnostics: &mut Diagnostics,
+) -> Vec<(Night, f32)> {
+    let mut positions = vec![];
+    for night in [Night::First, Night::Other] {
+        let wakes: Vec<&Wake> = wakes.iter().filter(|wake| wake.night == night).collect();
+        if wakes.is_empty() {
+            continue;
+        }
+
+        // The character it patches makes way for it
+        let position = |anchor: &str| {
+            character_list
+                .get(anchor)
+                .filter(|character| character.id != id)
+                .map(|character| night.position(character))
+                .filter(|position| *position > 0.0)
+        };
+        for wake in &wakes {
+            let (previous, next) = wake.relation.anchors();
+            for anchor in previous.into_iter().chain(next) {
+                if !character_list.contains_key(anchor) {
+                    diagnostics.error(
+                        &wake.path,
+                        wake.line,
+                        wake.column,
+                        format!("Could not find character with id {anchor}"),
+                    );
+                } else if position(anchor).is_none() {
+                    diagnostics.warning(
+                        &wake.path,
+                        wake.line,
+                        wake.column,
+                        format!("{anchor} does not wake on the {night}"),
+                    );
+                }
+            }
+        }
+
+        let waking: Vec<f32> = character_list
+            .keys()
+            .filter_map(|other| position(other))
+            .collect();
+        let placed = wakes.iter().find_map(|wake| {
+            let (previous, next) = wake.relation.anchors();
+            let previous = previous.map(position).unwrap_or(Some(0.0))?;
+            let next = next.map(position).unwrap_or(Some(f32::MAX))?;
+            if next <= previous {
+                diagnostics.error(
+                    &wake.path,
+                    wake.line,
+                    wake.column,
+                    format!("Night order on the {night} cannot fit between these characters"),
+                );
+                return None;
+            }
+
+            // Right next to the anchor, before anything else sharing the gap
+            let position = if previous > 0.0 || next == f32::MAX {
+                let next = waking
+                    .iter()
+                    .copied()
+                    .filter(|position| *position > previous)
+                    .fold(next.min(previous + 1.0), f32::min);
+                previous + STEP.min((next - previous) / 2.0)
+            } else {
+                let previous = waking
+                    .iter()
+                    .copied()
+                    .filter(|position| *position < next)
+                    .fold(0.0, f32::max);
+                next - STEP.min((next - previous) / 2.0)
+            };
+            Some(position)
+        });
+        positions.push((night, placed.unwrap_or_default()));
+    }
+
+    positions
+}
+
+/// Sorts one night, breaking ties by the position of what a character is anchored to, then by id
+fn night_positions(
+    night: Night,
+    character_list: &HashMap<String, Character>,
+    diagnostics: &mut Diagnostics,
+) -> HashMap<String, f32> {
+    let mut fixed: Vec<&Character> = character_list
+        .values()
+        .filter(|character| wakes(character, night).is_empty() && night.position(character) > 0.0)
+        .collect();
+    fixed.sort_by(|a, b| {
+        night
+            .position(a)
+            .total_cmp(&night.position(b))
+            .then_with(|| a.id.cmp(&b.id))
+    });
+    let mut custom: Vec<&Character> = character_list
+        .values()
+        .filter(|character| !wakes(character, night).is_empty())
+        .collect();
+    custom.sort_by(|a, b| a.id.cmp(&b.id));
+
+    for character in &custom {
+        for wake in wakes(character, night) {
+            let (previous, next) = wake.relation.anchors();
+            for id in previous.into_iter().chain(next) {
+                if !character_list.contains_key(id) {
+                    diagnostics.error(
+                        &wake.path,
+                        wake.line,
+                        wake.column,
+                        format!("Could not find character with id {id}"),
+                    );
+                }
+            }
+        }
+    }
+
+    // A homebrew character only wakes if everything on one of its lines does
+    let mut waking: HashSet<&str> = fixed
+        .iter()
+        .chain(&custom)
+        .map(|character| character.id.as_str())
+        .collect();
+    loop {
+        let before = waking.len();
+        for character in &custom {
+            let anchored = wakes(character, night).iter().any(|wake| {
+                let (previous, next) = wake.relation.anchors();
+                previous
+                    .into_iter()
+                    .chain(next)
+                    .all(|id| waking.contains(id))
+            });
+            if !anchored {
+                waking.remove(character.id.as_str());
+            }
+        }
+        if waking.len() == before {
+            break;
+        }
+    }
+    for character in &custom {
+        for wake in wakes(character, night) {
+            let (previous, next) = wake.relation.anchors();
+            for id in previous.into_iter().chain(next) {
+                if character_list.contains_key(id) && !waking.contains(id) {
+                    diagnostics.warning(
+                        &wake.path,
+                        wake.line,
+                        wake.column,
+                        format!("{id} does not wake on the {night}"),
+                    );
+                }
+            }
+        }
+    }
+
+    // Edges from `wakes` lines keep the line, the official order has none
+    let mut edges: Vec<(&str, &str, Option<&Wake>)> = fixed
+        .windows(2)
+        .map(|pair| (pair[0].id.as_str(), pair[1].id.as_str(), None))
+        .collect();
+    for character in custom
+        .iter()
+        .filter(|character| waking.contains(character.id.as_str()))
+    {
+        for wake in wakes(character, night) {
+            let (previous, next) = wake.relation.anchors();
+            if !previous
+                .into_iter()
+                .chain(next)
+                .all(|id| waking.contains(id))
+            {
+                continue;
+            }
+            if let Some(previous) = previous {
+                edges.push((previous, &character.id, Some(wake)));
+            }
+            if let Some(next) = next {
+                edges.push((&character.id, next, Some(wake)));
+            }
+        }
+    }
+
+    // Homebrew characters sort with the first character they are anchored to
+    let mut keys: HashMap<&str, f32> = fixed
+        .iter()
+        .map(|character| (character.id.as_str(), night.position(character)))
+        .collect();
+    loop {
+        let before = keys.len();
+        for character in &custom {
+            if keys.contains_key(character.id.as_str()) {
+                continue;
+            }
+            let anchor = wakes(character, night).iter().find_map(|wake| {
+                let (previous, next) = wake.relation.anchors();
+                keys.get(previous.or(next)?).copied()
+            });
+            if let Some(key) = anchor {
+                keys.insert(&character.id, key);
+            }
+        }
+        if keys.len() == before {
+            break;
+        }
+    }
+
+    let key = |id: &str| keys.get(id).copied().unwrap_or(f32::MAX);
+    let mut remaining: Vec<&str> = waking.iter().copied().collect();
+    let mut order: Vec<&str> = vec![];
+    let mut reported: HashSet<&str> = HashSet::new();
+    while !remaining.is_empty() {
+        let ready = remaining
+            .iter()
+            .copied()
+            .filter(|id| {
+                !edges
+                    .iter()
+                    .any(|(from, to, _)| to == id && remaining.contains(from))
+            })
+            .min_by(|a, b| key(a).total_cmp(&key(b)).then_with(|| a.cmp(b)));
+        let Some(ready) = ready else {
+            // Drop the line closing each cycle, so the rest of it and everything after still sort
+            for mut component in cycles(&remaining, &edges) {
+                let Some(wake) = edges.iter().rev().find_map(|(from, to, wake)| {
+                    wake.filter(|_| component.contains(from) && component.contains(to))
+                }) else {
+                    continue;
+                };
+                edges.retain(|(_, _, other)| !other.is_some_and(|other| std::ptr::eq(other, wake)));
+
+                if component.iter().all(|id| reported.contains(id)) {
+                    continue;
+                }
+                reported.extend(&component);
+                component.sort_by(|a, b| key(a).total_cmp(&key(b)).then_with(|| a.cmp(b)));
+                let names = component
+                    .iter()
+                    .map(|id| character_list[*id].name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                diagnostics.error(
+                    &wake.path,
+                    wake.line,
+                    wake.column,
+                    format!("Night order on the {night} has a cycle through {names}"),
+                );
+            }
+            continue;
+        };
+        remaining.retain(|id| *id != ready);
+        order.push(ready);
+    }
+
+    place(&order, character_list, night)
+}
+
+/// The strongly connected components of `nodes` that hold a cycle, including a node with an edge
+/// to itself
+fn cycles<'a>(nodes: &[&'a str], edges: &[(&'a str, &'a str, Option<&Wake>)]) -> Vec<Vec<&'a str>> {
+    let reachable = |start: &'a str| {
+        let mut seen: HashSet<&str> = HashSet::new();
+        let mut stack = vec![start];
+        while let Some(id) = stack.pop() {
+            for (_, to, _) in edges
+                .iter()
+                .filter(|(from, to, _)| *from == id && nodes.contains(to))
+            {
+                if seen.insert(to) {
+                    stack.push(to);
+                }
+            }
+        }
+        seen
+    };
+    let reachable: HashMap<&str, HashSet<&str>> =
+        nodes.iter().map(|id| (*id, reachable(id))).collect();
+
+    let mut components: Vec<Vec<&str>> = vec![];
+    let mut sorted = nodes.to_vec();
+    sorted.sort();
+    for id in sorted {
+        if !reachable[id].contains(id) || components.iter().flatten().any(|other| *other == id) {
+            continue;
+        }
+        components.push(
+            reachable[id]
+                .iter()
+                .copied()
+                .filter(|other| reachable[other].contains(id))
+                .collect(),
+        );
+    }
+    components
+}
+
+fn wakes(character: &Character, night: Night) -> Vec<&Wake> {
+    character
+        .wakes
+        .iter()
+        .filter(|wake| wake.night == night)
+        .collect()
+}
+
+/// Spreads the homebrew characters of `order` between the fixed positions around them. Fixed
+/// characters can share a position, like the Fabled, so a run ends at the next higher one.
+fn place(
+    order: &[&str],
+    character_list: &HashMap<String, Character>,
+    night: Night,
+) -> HashMap<String, f32> {
+    let fixed_position = |id: &str| {
+        let character = &character_list[id];
+        if character.wakes.iter().any(|wake| wake.night == night) {
+            None
+        } else {
+            Some(night.position(character))
+        }
+    };
+
+    let mut positions = HashMap::new();
+    let mut previous = 0.0;
+    let mut run: Vec<&str> = vec![];
+    for (idx, id) in order.iter().enumerate() {
+        match fixed_position(id) {
+            Some(position) => previous = f32::max(previous, position),
+            None => {
+                run.push(id);
+                if order
+                    .get(idx + 1)
+                    .is_none_or(|next| fixed_position(next).is_some())
+                {
+                    let next = order[idx + 1..]
+                        .iter()
+                        .filter_map(|id| fixed_position(id))
+                        .find(|position| *position > previous)
+                        .unwrap_or(previous + 1.0);
+                    let step = STEP.min((next - previous) / (run.len() + 1) as f32);
+                    for id in run.drain(..) {
+                        previous += step;
+                        positions.insert(id.to_owned(), previous);
+                    }
+                }
+            }
+        }
+    }
+
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, path::PathBuf};
+
+    use super::{parse_wakes, patched_positions, resolve_night_order, Night, Wake};
+    use crate::{
+        character::{
+            tests::{character, character_list},
+            Character,
+        },
+        diagnostics::Diagnostics,
+    };
+
+    fn official(id: &str, first_night: f32) -> Character {
+        let mut character = character(id, id, "townsfolk", "");
+        character.first_night = first_night;
+        character
+    }
+
+    /// A homebrew character with one `wakes` line per entry, starting at line 1
+    fn homebrew(id: &str, lines: &[&str]) -> Character {
+        let mut character = character(id, id, "townsfolk", "");
+        for (idx, value) in lines.iter().enumerate() {
+            let (nights, relation, column) = parse_wakes(value).unwrap();
+            for night in nights {
+                character.wakes.push(Wake {
+                    night,
+                    relation: relation.clone(),
+                    path: PathBuf::from(id),
+                    line: idx + 1,
+                    column,
+                });
+            }
+        }
+        character
+    }
+
+    fn resolve(characters: Vec<Character>) -> (HashMap<String, Character>, Diagnostics) {
+        let mut characters = character_list(characters);
+        let mut diagnostics = Diagnostics::default();
+        resolve_night_order(&mut characters, &mut diagnostics);
+        (characters, diagnostics)
+    }
+
+    fn first_night(characters: &HashMap<String, Character>, id: &str) -> f32 {
+        characters[id].first_night
+    }
+
+    #[test]
+    fn ties_break_by_anchor_then_id() {
+        let (characters, diagnostics) = resolve(vec![
+            official("a", 1.0),
+            official("b", 2.0),
+            homebrew("yak", &["first before b"]),
+            homebrew("zebra", &["first after a"]),
+            homebrew("xerus", &["first after a"]),
+        ]);
+
+        assert!(!diagnostics.has_errors());
+        let order = ["a", "xerus", "zebra", "yak", "b"].map(|id| first_night(&characters, id));
+        assert!(order.windows(2).all(|pair| pair[0] < pair[1]), "{order:?}");
+    }
+
+    #[test]
+    fn forward_references_sort_after_their_anchor() {
+        let (characters, diagnostics) = resolve(vec![
+            official("a", 1.0),
+            official("b", 2.0),
+            homebrew("p", &["first after q"]),
+            homebrew("q", &["first after a"]),
+        ]);
+
+        assert!(!diagnostics.has_errors());
+        let order = ["a", "q", "p", "b"].map(|id| first_night(&characters, id));
+        assert!(order.windows(2).all(|pair| pair[0] < pair[1]), "{order:?}");
+    }
+
+    #[test]
+    fn cycle_is_reported_once_and_downstream_still_wakes() {
+        let (characters, diagnostics) = resolve(vec![
+            official("a", 1.0),
+            homebrew("x", &["first after y"]),
+            homebrew("y", &["first after x"]),
+            homebrew("z", &["first after x"]),
+        ]);
+
+        let errors: Vec<_> = diagnostics.iter().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path, PathBuf::from("y"));
+        assert_eq!(
+            errors[0].message,
+            "Night order on the first night has a cycle through x, y"
+        );
+        assert!(first_night(&characters, "z") > first_night(&characters, "x"));
+    }
+
+    #[test]
+    fn self_reference_is_a_cycle() {
+        let (characters, diagnostics) =
+            resolve(vec![official("a", 1.0), homebrew("x", &["first after x"])]);
+
+        assert_eq!(diagnostics.iter().count(), 1);
+        assert!(first_night(&characters, "x") > 0.0);
+    }
+
+    #[test]
+    fn cycle_through_the_official_order_is_reported_on_the_closing_line() {
+        let (characters, diagnostics) = resolve(vec![
+            official("a", 1.0),
+            official("b", 2.0),
+            official("c", 3.0),
+            homebrew("x", &["first after c", "first before a"]),
+            homebrew("w", &["first after x"]),
+        ]);
+
+        let errors: Vec<_> = diagnostics.iter().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].path.to_str(), errors[0].line), (Some("x"), 2));
+        let order = ["c", "x", "w"].map(|id| first_night(&characters, id));
+        assert!(order.windows(2).all(|pair| pair[0] < pair[1]), "{order:?}");
+    }
+
+    #[test]
+    fn patched_wakes_sit_next_to_their_anchor() {
+        let (characters, _) = resolve(vec![
+            official("a", 1.0),
+            official("b", 2.0),
+            official("c", 3.0),
+            homebrew("x", &["first before b"]),
+        ]);
+        let mut diagnostics = Diagnostics::default();
+
+        let before = homebrew("c", &["first before b"]).wakes;
+        let positions = patched_positions("c", &before, &characters, &mut diagnostics);
+        let x = first_night(&characters, "x");
+        assert!(
+            matches!(positions[..], [(Night::First, position)] if position > x && position < 2.0)
+        );
+
+        // The patched character's own position does not hold it back
+        let after = homebrew("b", &["first after a"]).wakes;
+        let positions = patched_positions("b", &after, &characters, &mut diagnostics);
+        assert!(
+            matches!(positions[..], [(Night::First, position)] if position > 1.0 && position < x)
+        );
+
+        assert!(!diagnostics.has_errors());
+    }
+
+    #[test]
+    fn patched_wakes_on_unknown_characters_do_not_wake() {
+        let (characters, _) = resolve(vec![official("a", 1.0)]);
+        let mut diagnostics = Diagnostics::default();
+
+        let wakes = homebrew("a", &["first after unknown"]).wakes;
+        let positions = patched_positions("a", &wakes, &characters, &mut diagnostics);
+        assert!(matches!(positions[..], [(Night::First, 0.0)]));
+        assert!(diagnostics.has_errors());
+    }
+}
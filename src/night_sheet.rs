@@ -0,0 +1,88 @@
+use std::io::{self, Write};
+
+use crate::{
+    almanac::write_icon,
+    character::Character,
+    markup::{escape, render_reminder},
+    script::Script,
+    sheet::Paper,
+};
+
+const NIGHT_SHEET_STYLE: &str = include_str!("night_sheet_style.css");
+
+impl Script {
+    pub fn write_night_sheet<T>(
+        &self,
+        writer: &mut T,
+        paper: Paper,
+        first_night_special: &[&Character],
+        other_night_special: &[&Character],
+    ) -> io::Result<()>
+    where
+        T: Write,
+    {
+        write!(
+            writer,
+            "<!DOCTYPE html><html lang=\"en\"><head><meta charset=\"UTF-8\"><meta name=\"viewport\" content=\"width=device-width, initial-scale=1.0\"><title>{} - Night Sheet</title><style>{NIGHT_SHEET_STYLE}{}</style></head><body><div class=\"sheet\">",
+            escape(&self.name),
+            paper.page_rule()
+        )
+        ?;
+
+        write!(
+            writer,
+            "<header><h1>{}</h1><p class=\"author\">Night Sheet</p></header><div class=\"night-columns\">",
+            escape(&self.name)
+        )
+        ?;
+
+        write_night_column(
+            writer,
+            "FIRST NIGHT",
+            &self.first_night_order(first_night_special),
+            |character| &character.first_night_reminder,
+        )?;
+        write_night_column(
+            writer,
+            "OTHER NIGHTS",
+            &self.other_night_order(other_night_special),
+            |character| &character.other_night_reminder,
+        )?;
+
+        write!(writer, "</div></div></body></html>")?;
+
+        Ok(())
+    }
+}
+
+fn write_night_column<T, F>(
+    writer: &mut T,
+    header: &str,
+    characters: &[&Character],
+    reminder: F,
+) -> io::Result<()>
+where
+    T: Write,
+    F: Fn(&Character) -> &str,
+{
+    write!(writer, "<section><h2>{header}</h2>")?;
+
+    for character in characters {
+        write!(
+            writer,
+            "<div class=\"night-entry {}\">",
+            character.team.to_str()
+        )?;
+        write_icon(writer, character)?;
+        write!(
+            writer,
+            "<div><h3>{}</h3><p>{}</p></div></div>",
+            escape(&character.name),
+            render_reminder(reminder(character))
+        )?;
+    }
+
+    write!(writer, "</section>")?;
+
+    Ok(())
+}
@@ -1,59 +1,347 @@
-use std::{collections::HashMap, ffi::OsStr, fs::File, io::Read, path::Path};
+use std::{collections::HashMap, ffi::OsStr, fmt, fs::File, io::Read, iter, path::Path};
 
-use crate::{character::Jinx, script::Script};
+use crate::{
+    character::{parse_reminder, Character, Jinx, Team},
+    diagnostics::Diagnostics,
+    night_order::{parse_wakes, patched_positions, Night, Wake},
+    script::Script,
+};
 
+#[derive(Debug, Default, Clone)]
 pub struct Patch {
-    replace: Option<String>,
-    add: Vec<String>,
+    /// The last replacement whose guards hold is used
+    replace: Vec<Guarded>,
+    add: Vec<Guarded>,
     jinxes: Vec<Jinx>,
+    reminders: Vec<String>,
+    reminders_global: Vec<String>,
+    first_night_reminder: Option<String>,
+    other_night_reminder: Option<String>,
+    setup: Option<bool>,
+    wakes: Vec<Wake>,
+    /// Where `wakes` puts the character, filled in by `resolve_wakes`
+    positions: Vec<(Night, f32)>,
 }
 
-pub fn read_patches() -> HashMap<String, Patch> {
+impl Patch {
+    /// Reads one `<key> <value>` line of a patch file or script override block
+    pub fn read_line(
+        &mut self,
+        line: &str,
+        path: &Path,
+        line_no: usize,
+        character_list: &HashMap<String, Character>,
+        diagnostics: &mut Diagnostics,
+    ) {
+        let Some((key, value)) = line.split_once(' ') else {
+            diagnostics.warning(path, line_no, 1, format!("Unrecognised line {line}"));
+            return;
+        };
+
+        match key {
+            "add" | "replace" | "ability" => {
+                let guarded = match Guarded::parse(value) {
+                    Ok(guarded) => guarded,
+                    Err(err) => {
+                        diagnostics.error(path, line_no, key.len() + 2, err);
+                        return;
+                    }
+                };
+                for id in guarded.character_ids() {
+                    if !character_list.contains_key(id) {
+                        diagnostics.warning(
+                            path,
+                            line_no,
+                            key.len() + 2,
+                            format!("Condition on unknown character {id}"),
+                        );
+                    }
+                }
+
+                if key == "add" {
+                    self.add.push(guarded);
+                } else {
+                    if !self.replace.is_empty() && guarded.guards.is_empty() {
+                        diagnostics.warning(path, line_no, 1, "Ability is replaced more than once");
+                    }
+                    self.replace.push(guarded);
+                }
+            }
+            "reminder" | "globalreminder" => match parse_reminder(value) {
+                Ok((count, text)) => {
+                    let list = if key == "reminder" {
+                        &mut self.reminders
+                    } else {
+                        &mut self.reminders_global
+                    };
+                    list.extend(iter::repeat_n(text.to_owned(), count));
+                }
+                Err(err) => diagnostics.error(path, line_no, key.len() + 2, err),
+            },
+            "firstnight" | "othernight" => {
+                let (reminder, night) = if key == "firstnight" {
+                    (&mut self.first_night_reminder, Night::First)
+                } else {
+                    (&mut self.other_night_reminder, Night::Other)
+                };
+                if reminder.is_some() {
+                    diagnostics.warning(
+                        path,
+                        line_no,
+                        1,
+                        format!("Reminder for the {night} is replaced more than once"),
+                    );
+                }
+                *reminder = Some(value.to_owned());
+            }
+            "setup" => match value.parse() {
+                Ok(setup) => self.setup = Some(setup),
+                Err(_) => {
+                    diagnostics.error(path, line_no, key.len() + 2, "Setup must be true or false")
+                }
+            },
+            "wakes" => match parse_wakes(value) {
+                Ok((nights, relation, offset)) => {
+                    for night in nights {
+                        self.wakes.push(Wake {
+                            night,
+                            relation: relation.clone(),
+                            path: path.to_owned(),
+                            line: line_no,
+                            column: key.len() + 2 + offset,
+                        });
+                    }
+                }
+                Err((offset, message)) => {
+                    diagnostics.error(path, line_no, key.len() + 2 + offset, message)
+                }
+            },
+            "jinx" => {
+                if let Some((other, reason)) = value.split_once(' ') {
+                    if !character_list.contains_key(other) {
+                        diagnostics.warning(
+                            path,
+                            line_no,
+                            key.len() + 2,
+                            format!("Jinx with unknown character {other}"),
+                        );
+                    }
+                    self.jinxes.push(Jinx {
+                        id: other.to_owned(),
+                        reason: reason.to_owned(),
+                    })
+                } else {
+                    diagnostics.error(path, line_no, key.len() + 2, "Jinx is missing target id");
+                }
+            }
+            _ => diagnostics.error(path, line_no, 1, format!("Invalid key {key}")),
+        }
+    }
+
+    /// The replaced ability on a script of `characters`
+    fn replacement(&self, characters: &[(String, Team)]) -> Option<&str> {
+        self.replace
+            .iter()
+            .rev()
+            .find(|replace| replace.holds(characters))
+            .map(|replace| replace.text.as_str())
+    }
+
+    /// Whether applying the patch would change a character on a script of `characters`
+    fn changes(&self, characters: &[(String, Team)]) -> bool {
+        self.replacement(characters).is_some()
+            || self.add.iter().any(|add| add.holds(characters))
+            || !self.reminders.is_empty()
+            || !self.reminders_global.is_empty()
+            || self.first_night_reminder.is_some()
+            || self.other_night_reminder.is_some()
+            || self.setup.is_some()
+            || !self.positions.is_empty()
+            || self
+                .jinxes
+                .iter()
+                .any(|jinx| characters.iter().any(|(id, _)| *id == jinx.id))
+    }
+}
+
+/// A patch line with `if <condition>` and `unless <condition>` guards in front of its text
+#[derive(Debug, Clone)]
+struct Guarded {
+    guards: Vec<Guard>,
+    text: String,
+}
+
+#[derive(Debug, Clone)]
+struct Guard {
+    unless: bool,
+    condition: Condition,
+}
+
+/// Something a script has, either a character by id or any character of a team (`team:<team>`)
+#[derive(Debug, Clone)]
+enum Condition {
+    Character(String),
+    Team(Team),
+}
+
+impl Guarded {
+    fn parse(mut value: &str) -> Result<Guarded, String> {
+        let mut guards = vec![];
+        while let Some((word, rest)) = value.split_once(' ') {
+            let unless = match word {
+                "if" => false,
+                "unless" => true,
+                _ => break,
+            };
+            let Some((condition, rest)) = rest.split_once(' ') else {
+                return Err(format!("Missing text after condition {rest}"));
+            };
+            let condition = match condition.strip_prefix("team:") {
+                Some(team) => {
+                    let mut chars = team.chars();
+                    let team: String = chars
+                        .next()
+                        .map(|first| first.to_uppercase().chain(chars).collect())
+                        .unwrap_or_default();
+                    Condition::Team(team.parse()?)
+                }
+                None => Condition::Character(condition.to_owned()),
+            };
+            guards.push(Guard { unless, condition });
+            value = rest;
+        }
+
+        Ok(Guarded {
+            guards,
+            text: value.to_owned(),
+        })
+    }
+
+    fn character_ids(&self) -> impl Iterator<Item = &str> {
+        self.guards
+            .iter()
+            .filter_map(|guard| match &guard.condition {
+                Condition::Character(id) => Some(id.as_str()),
+                Condition::Team(_) => None,
+            })
+    }
+
+    /// Whether every guard holds on a script of `characters`
+    fn holds(&self, characters: &[(String, Team)]) -> bool {
+        self.guards.iter().all(|guard| {
+            let present = characters.iter().any(|(id, team)| match &guard.condition {
+                Condition::Character(other) => id == other,
+                Condition::Team(other) => team == other,
+            });
+            present != guard.unless
+        })
+    }
+}
+
+/// What `apply_patches` did to a script
+#[derive(Debug, Default, Clone)]
+pub struct PatchLog {
+    /// Each patched character by its new id, in the order they were patched
+    pub patched: Vec<(String, PatchReason)>,
+    pub ignored: Vec<IgnoredPatch>,
+}
+
+#[derive(Debug, Clone)]
+pub enum PatchReason {
+    /// A patch or override for the character itself
+    Direct,
+    /// A jinx with the character of this id, which was patched before it
+    Jinx(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchSource {
+    Bootlegger,
+    Override,
+}
+
+/// A patch, or a single line of one, that changed nothing on a script
+#[derive(Debug, Clone)]
+pub struct IgnoredPatch {
+    pub id: String,
+    pub source: PatchSource,
+    /// The ignored line, or `None` for the whole patch
+    pub line: Option<String>,
+    pub reason: String,
+}
+
+impl fmt::Display for PatchSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PatchSource::Bootlegger => "bootlegger",
+            PatchSource::Override => "override",
+        })
+    }
+}
+
+impl Patch {
+    /// The lines of the patch that change nothing on a script of `characters`
+    fn ignored(
+        &self,
+        id: &str,
+        source: PatchSource,
+        characters: &[(String, Team)],
+    ) -> Vec<IgnoredPatch> {
+        let ignored = |line: String, reason: String| IgnoredPatch {
+            id: id.to_owned(),
+            source,
+            line: Some(line),
+            reason,
+        };
+        let mut out = vec![];
+        for jinx in &self.jinxes {
+            if !characters.iter().any(|(other, _)| *other == jinx.id) {
+                out.push(ignored(
+                    format!("jinx {} {}", jinx.id, jinx.reason),
+                    format!("{} is not on the script", jinx.id),
+                ));
+            }
+        }
+        for (key, lines) in [("add", &self.add), ("replace", &self.replace)] {
+            for line in lines.iter().filter(|line| !line.holds(characters)) {
+                out.push(ignored(
+                    format!("{key} {}", line.text),
+                    String::from("Its condition does not hold"),
+                ));
+            }
+        }
+
+        out
+    }
+}
+
+pub fn read_patches(
+    dir: &str,
+    character_list: &HashMap<String, Character>,
+    diagnostics: &mut Diagnostics,
+) -> HashMap<String, Patch> {
     let mut patches = HashMap::new();
 
-    if let Ok(dir) = Path::new("script-gen").join("bootlegger").read_dir() {
+    if let Ok(dir) = Path::new(dir).read_dir() {
         for patch in dir.flatten() {
-            if let Some(Some(id)) = patch.path().file_stem().map(OsStr::to_str) {
+            let path = patch.path();
+            if let Some(Some(id)) = path.file_stem().map(OsStr::to_str) {
                 let mut buf = String::new();
-                File::open(patch.path())
-                    .unwrap_or_else(|_| panic!("Failed to open patch file for patch {id}",))
-                    .read_to_string(&mut buf)
-                    .unwrap_or_else(|_| panic!("Failed to read patch file for patch {id}",));
-
-                let mut replace = None;
-                let mut add = vec![];
-                let mut jinxes = vec![];
-
-                for line in buf.lines() {
-                    if let Some((key, value)) = line.split_once(' ') {
-                        match key {
-                            "add" => add.push(value.to_owned()),
-                            "replace" => {
-                                replace = Some(value.to_owned());
-                            }
-                            "jinx" => {
-                                if let Some((other, reason)) = value.split_once(' ') {
-                                    jinxes.push(Jinx {
-                                        id: other.to_owned(),
-                                        reason: reason.to_owned(),
-                                    })
-                                } else {
-                                    panic!("Jinx is missing target id in bootlegger patch for {id}")
-                                }
-                            }
-                            _ => panic!("Invalid key {key} for bootlegger patch for {id}"),
-                        }
+                if let Err(err) =
+                    File::open(&path).and_then(|mut file| file.read_to_string(&mut buf))
+                {
+                    diagnostics.error(&path, 0, 0, format!("Failed to read patch file: {err}"));
+                    continue;
+                }
+
+                let mut patch = Patch::default();
+                for (line_no, line) in buf.lines().enumerate().map(|(idx, line)| (idx + 1, line)) {
+                    if !line.is_empty() {
+                        patch.read_line(line, &path, line_no, character_list, diagnostics);
                     }
                 }
 
-                patches.insert(
-                    id.to_owned(),
-                    Patch {
-                        replace,
-                        add,
-                        jinxes,
-                    },
-                );
+                patches.insert(id.to_owned(), patch);
             }
         }
     }
@@ -61,40 +349,88 @@ pub fn read_patches() -> HashMap<String, Patch> {
     patches
 }
 
+/// Works out where the `wakes` lines of `patches` put their characters, from the night order
+/// resolved when the characters were loaded
+pub fn resolve_wakes(
+    patches: &mut HashMap<String, Patch>,
+    character_list: &HashMap<String, Character>,
+    diagnostics: &mut Diagnostics,
+) {
+    for (id, patch) in patches.iter_mut() {
+        if !patch.wakes.is_empty() {
+            patch.positions = patched_positions(id, &patch.wakes, character_list, diagnostics);
+        }
+    }
+}
+
 impl Script {
+    /// Applies the bootlegger patches, then the overrides of the script itself
     pub fn apply_patches(
         &mut self,
         patches: &HashMap<String, Patch>,
         image_list: &HashMap<String, Vec<String>>,
     ) {
-        if self
+        let contents: Vec<(String, Team)> = self
             .characters
             .iter()
-            .all(|character| patches.get(&character.id).is_none())
-        {
+            .map(|char| (char.id.clone(), char.team.clone()))
+            .collect();
+
+        let mut sources: Vec<(&String, &Patch, PatchSource)> = patches
+            .iter()
+            .map(|(id, patch)| (id, patch, PatchSource::Bootlegger))
+            .chain(
+                self.overrides
+                    .iter()
+                    .map(|(id, patch)| (id, patch, PatchSource::Override)),
+            )
+            .collect();
+        sources.sort_by(|a, b| a.0.cmp(b.0));
+        for (id, patch, source) in sources {
+            if contents.iter().any(|(other, _)| other == id) {
+                self.patch_log
+                    .ignored
+                    .extend(patch.ignored(id, source, &contents));
+            } else {
+                self.patch_log.ignored.push(IgnoredPatch {
+                    id: id.to_owned(),
+                    source,
+                    line: None,
+                    reason: format!("{id} is not on the script"),
+                });
+            }
+        }
+
+        if self.characters.iter().all(|character| {
+            !patches.contains_key(&character.id) && !self.overrides.contains_key(&character.id)
+        }) {
             return;
         }
 
-        let character_ids: Vec<String> =
-            self.characters.iter().map(|char| char.id.clone()).collect();
+        let character_ids: Vec<String> = contents.iter().map(|(id, _)| id.clone()).collect();
         let mut patched_character_ids = vec![];
 
         for character in self.characters.iter_mut() {
-            if let Some(patch) = patches.get(&character.id) {
-                if patch.replace.is_none()
-                    && patch.add.is_empty()
-                    && patch
-                        .jinxes
-                        .iter()
-                        .all(|jinx| !character_ids.contains(&jinx.id))
-                {
-                    continue;
-                }
+            let character_patches: Vec<&Patch> = [
+                patches.get(&character.id),
+                self.overrides.get(&character.id),
+            ]
+            .into_iter()
+            .flatten()
+            .filter(|patch| patch.changes(&contents))
+            .collect();
+            if character_patches.is_empty() {
+                continue;
+            }
 
-                patched_character_ids.push(character.id.clone());
-                character.patched = true;
-                character.id = format!("patched_{}", character.id);
+            patched_character_ids.push(character.id.clone());
+            character.patched = true;
+            character.id = format!("patched_{}", character.id);
+            self.patch_log
+                .patched
+                .push((character.id.clone(), PatchReason::Direct));
 
+            for patch in character_patches {
                 for jinx in &patch.jinxes {
                     if character_ids.contains(&jinx.id) {
                         if let Some(existing_idx) = character
@@ -109,19 +445,43 @@ impl Script {
                     }
                 }
 
-                for add in &patch.add {
+                for add in patch.add.iter().filter(|add| add.holds(&contents)) {
                     character.jinxes.push(Jinx {
                         id: character.id.clone(),
-                        reason: add.to_owned(),
+                        reason: add.text.to_owned(),
                     })
                 }
 
-                if let Some(replace) = &patch.replace {
+                character.reminders.extend(patch.reminders.iter().cloned());
+                character
+                    .reminders_global
+                    .extend(patch.reminders_global.iter().cloned());
+                if let Some(reminder) = &patch.first_night_reminder {
+                    character.first_night_reminder = reminder.to_owned();
+                }
+                if let Some(reminder) = &patch.other_night_reminder {
+                    character.other_night_reminder = reminder.to_owned();
+                }
+                if let Some(setup) = patch.setup {
+                    character.setup = setup;
+                }
+                for (night, position) in &patch.positions {
+                    *night.position_mut(character) = *position;
+                }
+
+                if let Some(replace) = patch.replacement(&contents) {
                     character.ability = replace.to_owned();
-                    character.jinxes.push(Jinx {
-                        id: character.id.clone(),
-                        reason: String::from("This character has a modified ability."),
-                    })
+                    let reason = "This character has a modified ability.";
+                    if !character
+                        .jinxes
+                        .iter()
+                        .any(|jinx| jinx.id == character.id && jinx.reason == reason)
+                    {
+                        character.jinxes.push(Jinx {
+                            id: character.id.clone(),
+                            reason: String::from(reason),
+                        })
+                    }
                 }
             }
         }
@@ -137,6 +497,12 @@ impl Script {
                     .any(|jinx| patched_character_ids.contains(&jinx.id))
                 {
                     if !character.patched {
+                        let with = character
+                            .jinxes
+                            .iter()
+                            .find(|jinx| patched_character_ids.contains(&jinx.id))
+                            .map(|jinx| jinx.id.clone())
+                            .unwrap_or_default();
                         patched_character_ids.push(character.id.clone());
                         character.patched = true;
                         require_iter = true;
@@ -145,6 +511,9 @@ impl Script {
                         }
 
                         character.id = format!("patched_{}", character.id);
+                        self.patch_log
+                            .patched
+                            .push((character.id.clone(), PatchReason::Jinx(with)));
                     }
 
                     for jinx in character.jinxes.iter_mut() {
@@ -157,3 +526,57 @@ impl Script {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, path::Path};
+
+    use super::Patch;
+    use crate::{
+        character::tests::{character, character_list},
+        diagnostics::Diagnostics,
+        script::Script,
+    };
+
+    #[test]
+    fn reminders_take_a_count() {
+        let characters =
+            character_list(vec![character("monk", "Monk", "townsfolk", "Each night*")]);
+        let mut diagnostics = Diagnostics::default();
+        let mut patch = Patch::default();
+        for line in ["reminder 2 Safe", "globalreminder 1 Is The Monk"] {
+            patch.read_line(line, Path::new("monk"), 1, &characters, &mut diagnostics);
+        }
+        let mut script = Script::parse(
+            "test",
+            "Test\nTester\n\nmonk",
+            &characters,
+            &mut diagnostics,
+        )
+        .unwrap();
+        script.apply_patches(
+            &HashMap::from([(String::from("monk"), patch)]),
+            &HashMap::new(),
+        );
+
+        assert_eq!(diagnostics.iter().count(), 0);
+        assert_eq!(script.characters[0].reminders, ["Safe", "Safe"]);
+        assert_eq!(script.characters[0].reminders_global, ["Is The Monk"]);
+    }
+
+    #[test]
+    fn reminders_without_a_count_are_errors() {
+        let mut diagnostics = Diagnostics::default();
+        let mut patch = Patch::default();
+        patch.read_line(
+            "reminder Safe",
+            Path::new("monk"),
+            1,
+            &HashMap::new(),
+            &mut diagnostics,
+        );
+
+        assert!(diagnostics.has_errors());
+        assert!(patch.reminders.is_empty());
+    }
+}
@@ -1,20 +1,45 @@
 use std::{
+    cmp::Ordering,
     collections::HashMap,
     fs::File,
-    io::{Read, Write},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
 };
 
 use serde_json::{Map, Value};
 
-use crate::{almanac::AlmanacFields, character::Character};
+use crate::{
+    almanac::AlmanacFields,
+    character::Character,
+    config::Config,
+    diagnostics::Diagnostics,
+    lint::Lint,
+    patch::{Patch, PatchLog},
+    setup::SetupModifier,
+};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Script {
     pub name: String,
     pub author: String,
+    pub source: String,
     pub characters: Vec<Character>,
     pub bootlegger_rules: Vec<String>,
     pub almanac: AlmanacFields,
+    pub add_djinn: bool,
+    pub add_bootlegger: bool,
+    pub setup_modifiers: Vec<SetupModifier>,
+    pub allowed_lints: Vec<Lint>,
+    /// The line each listed character is on, characters from the base are on the `base` line and
+    /// characters added later are not in here
+    pub lines: HashMap<String, usize>,
+    pub duplicates: Vec<(String, usize)>,
+    /// The source file this script is a variant of, as named by its `base` line
+    pub base: Option<String>,
+    /// Patches from `override` blocks, applied to this script only
+    pub overrides: HashMap<String, Patch>,
+    /// What `apply_patches` changed and left out
+    pub patch_log: PatchLog,
 }
 
 static SORT_ORDER: [&str; 35] = [
@@ -55,61 +80,329 @@ static SORT_ORDER: [&str; 35] = [
     "Minions",
 ];
 
+/// Scripts already read, by path, so a base shared by several variants is read and checked once
+#[derive(Debug, Default)]
+pub struct ScriptCache {
+    scripts: HashMap<PathBuf, Option<Script>>,
+}
+
 impl Script {
-    pub fn from_source(source: &str, character_list: &HashMap<String, Character>) -> Script {
+    /// Reads a script source, or takes it from `cache` if it was read before
+    pub fn from_source(
+        source: &str,
+        character_list: &HashMap<String, Character>,
+        cache: &mut ScriptCache,
+        diagnostics: &mut Diagnostics,
+    ) -> Option<Script> {
+        let path = PathBuf::from(source);
+        if let Some(script) = cache.scripts.get(&path) {
+            return script.clone();
+        }
+
         let mut buf = String::new();
-        File::open(source)
-            .unwrap_or_else(|_| panic!("Failed to open script source file for script {source}"))
-            .read_to_string(&mut buf)
-            .unwrap_or_else(|_| panic!("Failed to read script source file for script {source}"));
-
-        let mut lines = buf.lines();
-
-        let name = lines
-            .next()
-            .unwrap_or_else(|| panic!("Script {source} does not have a name"))
-            .to_owned();
-        let author = lines
-            .next()
-            .unwrap_or_else(|| panic!("Script {source} does not have an author"))
-            .to_owned();
+        if let Err(err) = File::open(source).and_then(|mut file| file.read_to_string(&mut buf)) {
+            diagnostics.error(
+                source,
+                0,
+                0,
+                format!("Failed to read script source file: {err}"),
+            );
+            return None;
+        }
+
+        let script = Script::parse_variant(
+            source,
+            &buf,
+            character_list,
+            diagnostics,
+            &mut vec![path.clone()],
+            cache,
+        );
+        cache.scripts.insert(path, script.clone());
+        script
+    }
+
+    /// Parses script source text, with `source` naming it in diagnostics
+    pub fn parse(
+        source: &str,
+        text: &str,
+        character_list: &HashMap<String, Character>,
+        diagnostics: &mut Diagnostics,
+    ) -> Option<Script> {
+        Script::parse_variant(
+            source,
+            text,
+            character_list,
+            diagnostics,
+            &mut vec![PathBuf::from(source)],
+            &mut ScriptCache::default(),
+        )
+    }
+
+    /// Parses script source text, where `chain` holds the sources whose bases are being read
+    fn parse_variant(
+        source: &str,
+        text: &str,
+        character_list: &HashMap<String, Character>,
+        diagnostics: &mut Diagnostics,
+        chain: &mut Vec<PathBuf>,
+        cache: &mut ScriptCache,
+    ) -> Option<Script> {
+        let mut lines = text.lines().enumerate().map(|(idx, line)| (idx + 1, line));
+
+        let Some((_, name)) = lines.next() else {
+            diagnostics.error(source, 1, 1, "Script does not have a name");
+            return None;
+        };
+        let Some((_, author)) = lines.next() else {
+            diagnostics.error(source, 2, 1, "Script does not have an author");
+            return None;
+        };
+        let name = name.to_owned();
+        let author = author.to_owned();
         let mut almanac = AlmanacFields::default();
-        let mut characters = vec![];
+        let mut characters: Vec<Character> = vec![];
         let mut bootlegger_rules = vec![];
         let mut sort_characters = true;
+        let mut add_djinn = true;
+        let mut add_bootlegger = true;
+        let mut allowed_lints = vec![];
+        let mut character_lines = HashMap::new();
+        let mut duplicates = vec![];
+        let mut base = None;
+        let mut removed = vec![];
+        let mut replaced = vec![];
+        let mut overrides: HashMap<String, Patch> = HashMap::new();
+        let mut override_lines = vec![];
 
-        while let Some(line) = lines.next() {
+        while let Some((line_no, line)) = lines.next() {
             match line.split_once(' ') {
+                Some(("base", other)) => {
+                    if base.is_some() {
+                        diagnostics.error(source, line_no, 1, "Script already has a base");
+                    } else {
+                        base = Some((other.to_owned(), line_no));
+                    }
+                }
+                Some(("override", id)) => {
+                    if overrides.contains_key(id) {
+                        diagnostics.warning(
+                            source,
+                            line_no,
+                            10,
+                            format!("{id} is overridden more than once"),
+                        );
+                    }
+                    override_lines.push((id.to_owned(), line_no));
+                    let patch = overrides.entry(id.to_owned()).or_default();
+                    for (line_no, line) in lines.by_ref() {
+                        if line.is_empty() {
+                            break;
+                        }
+                        patch.read_line(
+                            line,
+                            Path::new(source),
+                            line_no,
+                            character_list,
+                            diagnostics,
+                        );
+                    }
+                }
+                Some(("remove", id)) => removed.push((id.to_owned(), line_no)),
+                Some(("replace", ids)) => match ids.split_once(' ') {
+                    Some((old, new)) => replaced.push((old.to_owned(), new.to_owned(), line_no)),
+                    None => diagnostics.error(
+                        source,
+                        line_no,
+                        9,
+                        "Replace must be followed by two character ids",
+                    ),
+                },
                 Some(("bootlegger", rule)) => {
                     bootlegger_rules.push(rule.to_string());
                 }
+                Some(("allow", lint)) => match lint.parse() {
+                    Ok(lint) => allowed_lints.push(lint),
+                    Err(err) => diagnostics.warning(source, line_no, 7, err),
+                },
+                Some(("difficulty", difficulty)) => {
+                    almanac.difficulty = Some(difficulty.to_owned());
+                }
+                Some(("players", players)) => match parse_players(players) {
+                    Some(players) => almanac.players = Some(players),
+                    None => diagnostics.error(
+                        source,
+                        line_no,
+                        9,
+                        "Players must be <count> or <min>-<max>",
+                    ),
+                },
+                Some(("tags", tags)) => {
+                    almanac.tags.extend(
+                        tags.split(',')
+                            .map(str::trim)
+                            .filter(|tag| !tag.is_empty())
+                            .map(String::from),
+                    );
+                }
                 _ => match line {
-                    "intro" => {
-                        for line in lines.by_ref() {
-                            if line.is_empty() {
-                                break;
-                            }
-                            almanac.intro.push(line.to_string());
-                        }
-                    }
+                    "intro" => almanac.intro.extend(read_block(&mut lines)),
+                    "themes" => almanac.themes.extend(read_block(&mut lines)),
+                    "notes" => almanac.notes.extend(read_block(&mut lines)),
+                    "changelog" => almanac.changelog.extend(read_block(&mut lines)),
+                    "playtesting" => almanac.playtesting.extend(read_block(&mut lines)),
                     "keeporder" => {
                         sort_characters = false;
                     }
+                    "nodjinn" => {
+                        add_djinn = false;
+                    }
+                    "nobootlegger" => {
+                        add_bootlegger = false;
+                    }
                     "" => (),
-                    _ => characters.push(
-                        character_list
-                            .get(line)
-                            .unwrap_or_else(|| {
-                                panic!(
-                                    "Failed to find data for character {line} in script {source}"
-                                )
-                            })
-                            .clone(),
-                    ),
+                    _ => {
+                        if let Some(character) = character_list.get(line) {
+                            // Listed twice is still written out twice, the lint reports it
+                            if characters.iter().any(|character| character.id == line) {
+                                duplicates.push((line.to_owned(), line_no));
+                            } else {
+                                character_lines.insert(line.to_owned(), line_no);
+                            }
+                            characters.push(character.clone());
+                        } else {
+                            diagnostics.error(
+                                source,
+                                line_no,
+                                1,
+                                format!("Failed to find data for character {line}"),
+                            );
+                        }
+                    }
                 },
             }
         }
 
+        let base = base.and_then(|(other, line_no)| {
+            let path = Path::new(source).with_file_name(&other);
+            if let Some(start) = chain.iter().position(|source| *source == path) {
+                let cycle: Vec<_> = chain[start..]
+                    .iter()
+                    .chain([&path])
+                    .map(|source| file_name(source))
+                    .collect();
+                diagnostics.error(
+                    source,
+                    line_no,
+                    6,
+                    format!("Script bases form a cycle: {}", cycle.join(" -> ")),
+                );
+                return None;
+            }
+            if let Some(script) = cache.scripts.get(&path) {
+                return script.clone().map(|script| (other, line_no, script));
+            }
+
+            let mut buf = String::new();
+            if let Err(err) = File::open(&path).and_then(|mut file| file.read_to_string(&mut buf)) {
+                diagnostics.error(
+                    source,
+                    line_no,
+                    6,
+                    format!("Failed to read base script {other}: {err}"),
+                );
+                return None;
+            }
+
+            chain.push(path.clone());
+            let script = Script::parse_variant(
+                &path.to_string_lossy(),
+                &buf,
+                character_list,
+                diagnostics,
+                chain,
+                cache,
+            );
+            chain.pop();
+            cache.scripts.insert(path, script.clone());
+            script.map(|script| (other, line_no, script))
+        });
+        let base = base.map(|(other, base_line, script)| {
+            for character in &characters {
+                // A second listing is already a duplicate of the first
+                if !script.characters.iter().any(|base| base.id == character.id) {
+                    continue;
+                }
+                if let Some(line_no) = character_lines.remove(&character.id) {
+                    duplicates.push((character.id.to_owned(), line_no));
+                }
+            }
+            characters.retain(|character| character_lines.contains_key(&character.id));
+            for character in &script.characters {
+                character_lines.insert(character.id.to_owned(), base_line);
+            }
+            characters.splice(0..0, script.characters);
+            bootlegger_rules.splice(0..0, script.bootlegger_rules);
+            allowed_lints.extend(script.allowed_lints);
+            almanac.inherit(script.almanac);
+            for (id, patch) in script.overrides {
+                overrides.entry(id).or_insert(patch);
+            }
+            add_djinn &= script.add_djinn;
+            add_bootlegger &= script.add_bootlegger;
+            other
+        });
+
+        for (id, line_no) in removed {
+            if let Some(idx) = characters.iter().position(|character| character.id == id) {
+                characters.remove(idx);
+                character_lines.remove(&id);
+            } else {
+                diagnostics.warning(
+                    source,
+                    line_no,
+                    8,
+                    format!("Cannot remove {id}, it is not on the script"),
+                );
+            }
+        }
+        for (old, new, line_no) in replaced {
+            let Some(idx) = characters.iter().position(|character| character.id == old) else {
+                diagnostics.warning(
+                    source,
+                    line_no,
+                    9,
+                    format!("Cannot replace {old}, it is not on the script"),
+                );
+                continue;
+            };
+            if characters.iter().any(|character| character.id == new) {
+                duplicates.push((new, line_no));
+            } else if let Some(character) = character_list.get(&new) {
+                character_lines.remove(&old);
+                character_lines.insert(new, line_no);
+                characters[idx] = character.clone();
+            } else {
+                diagnostics.error(
+                    source,
+                    line_no,
+                    old.len() + 10,
+                    format!("Failed to find data for character {new}"),
+                );
+            }
+        }
+
+        for (id, line_no) in override_lines {
+            if !characters.iter().any(|character| character.id == id) {
+                diagnostics.warning(
+                    source,
+                    line_no,
+                    10,
+                    format!("Cannot override {id}, it is not on the script"),
+                );
+            }
+        }
+
         if sort_characters {
             characters.sort_unstable_by(|a, b| {
                 let cmp = a.team.cmp(&b.team);
@@ -134,36 +427,56 @@ impl Script {
                     return cmp;
                 }
 
-                return a.name.cmp(&b.name);
+                a.name.cmp(&b.name)
             });
         }
 
-        Script {
+        Some(Script {
             name,
             author,
+            source: source.to_owned(),
             characters,
             bootlegger_rules,
             almanac,
-        }
+            add_djinn,
+            add_bootlegger,
+            setup_modifiers: vec![],
+            allowed_lints,
+            lines: character_lines,
+            duplicates,
+            base,
+            overrides,
+            patch_log: PatchLog::default(),
+        })
     }
 
-    pub fn resolve_required(&mut self, character_list: &HashMap<String, Character>) {
-        let mut to_add = vec![];
+    pub fn resolve_required(
+        &mut self,
+        character_list: &HashMap<String, Character>,
+        diagnostics: &mut Diagnostics,
+    ) {
+        let mut to_add: Vec<Character> = vec![];
 
         for character in &self.characters {
             for required in &character.required_characters {
                 if !self
                     .characters
                     .iter()
+                    .chain(&to_add)
                     .any(|character| character.id == *required)
                 {
                     if let Some(required) = character_list.get(required) {
                         to_add.push(required.to_owned());
                     } else {
-                        panic!(
-                            "Could not find required character {required} for {}",
-                            character.id
-                        )
+                        diagnostics.error(
+                            &self.source,
+                            0,
+                            0,
+                            format!(
+                                "Could not find required character {required} for {}",
+                                character.id
+                            ),
+                        );
                     }
                 }
             }
@@ -171,15 +484,72 @@ impl Script {
 
         if !to_add.is_empty() {
             self.characters.extend(to_add);
-            self.resolve_required(character_list);
+            self.resolve_required(character_list, diagnostics);
+        }
+    }
+
+    pub fn add_fabled(&mut self, character_list: &HashMap<String, Character>) {
+        let has_jinxes = self.characters.iter().any(|character| {
+            character.jinxes.iter().any(|jinx| {
+                jinx.id != character.id && self.characters.iter().any(|other| other.id == jinx.id)
+            })
+        });
+        let has_homebrew = !self.bootlegger_rules.is_empty()
+            || self
+                .characters
+                .iter()
+                .any(|character| !character.official || character.patched);
+
+        for (id, add) in [
+            ("djinn", self.add_djinn && has_jinxes),
+            ("bootlegger", self.add_bootlegger && has_homebrew),
+        ] {
+            if !add || self.characters.iter().any(|character| character.id == id) {
+                continue;
+            }
+            if let Some(fabled) = character_list.get(id) {
+                self.characters.push(fabled.to_owned());
+            }
         }
     }
 
-    pub fn write_json<T>(&self, writer: &mut T, file_name: &str)
+    pub fn first_night_order<'a>(&'a self, special: &[&'a Character]) -> Vec<&'a Character> {
+        let mut sorted: Vec<_> = self
+            .characters
+            .iter()
+            .filter(|character| character.first_night > 0.0)
+            .collect();
+        sorted.extend(special);
+        sorted.sort_unstable_by(|a, b| {
+            a.first_night
+                .partial_cmp(&b.first_night)
+                .unwrap_or(Ordering::Equal)
+        });
+
+        sorted
+    }
+
+    pub fn other_night_order<'a>(&'a self, special: &[&'a Character]) -> Vec<&'a Character> {
+        let mut sorted: Vec<_> = self
+            .characters
+            .iter()
+            .filter(|character| character.other_night > 0.0)
+            .collect();
+        sorted.extend(special);
+        sorted.sort_unstable_by(|a, b| {
+            a.other_night
+                .partial_cmp(&b.other_night)
+                .unwrap_or(Ordering::Equal)
+        });
+
+        sorted
+    }
+
+    pub fn write_json<T>(&self, writer: &mut T, file_name: &str, config: &Config) -> io::Result<()>
     where
         T: Write,
     {
-        let mut out: Vec<Value> = vec![self.meta(file_name)];
+        let mut out: Vec<Value> = vec![self.meta(file_name, config)];
 
         for character in &self.characters {
             if character.official && !character.patched {
@@ -193,11 +563,11 @@ impl Script {
             }
         }
 
-        serde_json::to_writer(writer, &out)
-            .unwrap_or_else(|_| panic!("Failed to generate json for script {}", self.name))
+        serde_json::to_writer(writer, &out)?;
+        Ok(())
     }
 
-    fn meta(&self, file_name: &str) -> Value {
+    pub fn meta(&self, file_name: &str, config: &Config) -> Value {
         let mut map = Map::new();
 
         map.insert(String::from("id"), Value::String(String::from("_meta")));
@@ -209,7 +579,9 @@ impl Script {
         map.insert(
             String::from("almanac"),
             Value::String(format!(
-                "https://totox00.github.io/botc-scripts/{file_name}.html"
+                "{}{}",
+                config.urls.almanac,
+                config.output.almanac(file_name)
             )),
         );
         if !self.bootlegger_rules.is_empty() {
@@ -229,9 +601,36 @@ impl Script {
     }
 }
 
+pub fn file_name(source: &Path) -> String {
+    source
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| source.to_string_lossy().into_owned())
+}
+
+/// Reads lines up to the next empty line
+fn read_block<'a, I>(lines: &mut I) -> Vec<String>
+where
+    I: Iterator<Item = (usize, &'a str)>,
+{
+    lines
+        .map(|(_, line)| line)
+        .take_while(|line| !line.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Parses `10` or `7-12`
+fn parse_players(players: &str) -> Option<(usize, usize)> {
+    let (min, max) = players.split_once('-').unwrap_or((players, players));
+    let min = min.trim().parse().ok()?;
+    let max = max.trim().parse().ok()?;
+    (min <= max).then_some((min, max))
+}
+
 fn get_sort_idx(ability: &str) -> usize {
-    for idx in 0..SORT_ORDER.len() {
-        if ability.starts_with(SORT_ORDER[idx]) {
+    for (idx, prefix) in SORT_ORDER.iter().enumerate() {
+        if ability.starts_with(prefix) {
             if let Some(next) = SORT_ORDER.get(idx + 1) {
                 if !ability.starts_with(next) {
                     return idx;
@@ -244,3 +643,151 @@ fn get_sort_idx(ability: &str) -> usize {
 
     SORT_ORDER.len()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs, path::PathBuf};
+
+    use super::{Script, ScriptCache};
+    use crate::{
+        character::{
+            tests::{character, character_list},
+            Character,
+        },
+        diagnostics::Diagnostics,
+    };
+
+    fn characters() -> HashMap<String, Character> {
+        character_list(vec![
+            character("chef", "Chef", "townsfolk", "You start knowing"),
+            character("empath", "Empath", "townsfolk", "Each night"),
+            character("monk", "Monk", "townsfolk", "Each night*"),
+            character("saint", "Saint", "outsider", "If you die"),
+            character("imp", "Imp", "demon", "Each night*"),
+        ])
+    }
+
+    /// Writes `files` to a directory of their own, so `base` lines can find each other
+    fn sources(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("script-gen-{}-{test}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, text) in files {
+            fs::write(dir.join(name), text).unwrap();
+        }
+        dir
+    }
+
+    fn ids(script: &Script) -> Vec<&str> {
+        let mut ids: Vec<_> = script
+            .characters
+            .iter()
+            .map(|character| character.id.as_str())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn variant_removes_and_replaces_base_characters() {
+        let dir = sources(
+            "variant",
+            &[
+                ("base", "Base\nTester\n\nchef\nempath\nimp\n"),
+                (
+                    "variant",
+                    "Variant\nTester\nbase base\nremove chef\nreplace empath monk\n\nsaint\n",
+                ),
+            ],
+        );
+        let mut diagnostics = Diagnostics::default();
+        let script = Script::from_source(
+            &dir.join("variant").to_string_lossy(),
+            &characters(),
+            &mut ScriptCache::default(),
+            &mut diagnostics,
+        )
+        .unwrap();
+
+        assert_eq!(diagnostics.iter().count(), 0);
+        assert_eq!(script.base.as_deref(), Some("base"));
+        assert_eq!(ids(&script), ["imp", "monk", "saint"]);
+        assert_eq!(script.lines["imp"], 3);
+        assert_eq!(script.lines["monk"], 5);
+        assert_eq!(script.lines["saint"], 7);
+    }
+
+    #[test]
+    fn variant_relisting_a_base_character_is_a_duplicate() {
+        let dir = sources(
+            "duplicate",
+            &[
+                ("base", "Base\nTester\n\nchef\nimp\n"),
+                ("variant", "Variant\nTester\nbase base\n\nchef\nchef\n"),
+            ],
+        );
+        let mut diagnostics = Diagnostics::default();
+        let script = Script::from_source(
+            &dir.join("variant").to_string_lossy(),
+            &characters(),
+            &mut ScriptCache::default(),
+            &mut diagnostics,
+        )
+        .unwrap();
+
+        assert_eq!(ids(&script), ["chef", "imp"]);
+        let mut duplicates = script.duplicates.clone();
+        duplicates.sort();
+        assert_eq!(
+            duplicates,
+            [(String::from("chef"), 5), (String::from("chef"), 6)]
+        );
+    }
+
+    #[test]
+    fn base_errors_are_reported_once() {
+        let dir = sources(
+            "errors",
+            &[
+                ("base", "Base\nTester\n\nchef\nunknown\n"),
+                ("variant", "Variant\nTester\nbase base\n"),
+            ],
+        );
+        let mut diagnostics = Diagnostics::default();
+        let mut cache = ScriptCache::default();
+        for source in ["variant", "base", "variant"] {
+            Script::from_source(
+                &dir.join(source).to_string_lossy(),
+                &characters(),
+                &mut cache,
+                &mut diagnostics,
+            );
+        }
+
+        let messages: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+        assert_eq!(messages, ["Failed to find data for character unknown"]);
+    }
+
+    #[test]
+    fn base_cycle_is_an_error() {
+        let dir = sources(
+            "cycle",
+            &[
+                ("first", "First\nTester\nbase second\n\nchef\n"),
+                ("second", "Second\nTester\nbase first\n\nimp\n"),
+            ],
+        );
+        let mut diagnostics = Diagnostics::default();
+        Script::from_source(
+            &dir.join("first").to_string_lossy(),
+            &characters(),
+            &mut ScriptCache::default(),
+            &mut diagnostics,
+        );
+
+        assert!(diagnostics.iter().any(|diagnostic| diagnostic.message
+            == "Script bases form a cycle: first -> second -> first"));
+    }
+}
@@ -0,0 +1,608 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    io::{self, Write},
+};
+
+use serde::Serialize;
+use serde_json::json;
+
+use crate::{
+    character::{Character, Team},
+    script::Script,
+};
+
+/// Player counts covered by the setup table, with their base distribution
+pub const BASE_DISTRIBUTIONS: [(usize, Distribution); 11] = [
+    (5, Distribution::new(3, 0, 1, 1)),
+    (6, Distribution::new(3, 1, 1, 1)),
+    (7, Distribution::new(5, 0, 1, 1)),
+    (8, Distribution::new(5, 1, 1, 1)),
+    (9, Distribution::new(5, 2, 1, 1)),
+    (10, Distribution::new(7, 0, 2, 1)),
+    (11, Distribution::new(7, 1, 2, 1)),
+    (12, Distribution::new(7, 2, 2, 1)),
+    (13, Distribution::new(9, 0, 3, 1)),
+    (14, Distribution::new(9, 1, 3, 1)),
+    (15, Distribution::new(9, 2, 3, 1)),
+];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetupModifier {
+    pub character: String,
+    pub team: Team,
+    /// Bag-disabled characters are not dealt to a player, so their own slot makes up the
+    /// difference instead of a Townsfolk, like the Lil' Monsta's Minion replacing the Demon
+    #[serde(skip_serializing_if = "is_false")]
+    pub bag_disabled: bool,
+    pub text: String,
+    pub changes: Vec<SetupChange>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetupChange {
+    /// The team whose count changes, Townsfolk make up the difference
+    pub team: Team,
+    /// The character added, if the clause names one rather than a team
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub character: Option<String>,
+    /// The changes the storyteller can choose between, read as `kind` says
+    pub options: Vec<i32>,
+    #[serde(skip_serializing_if = "ChangeKind::is_by")]
+    pub kind: ChangeKind,
+    /// Whatever follows the team or character, like `of opposing alignment`
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub qualifier: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChangeKind {
+    /// The options are added to the count, like `+1 Outsider`
+    By,
+    /// The options are the count, like `No Demon`
+    Exactly,
+    /// The options are added to a bare majority of the players, like `Most players are Legion`
+    Majority,
+}
+
+impl ChangeKind {
+    fn is_by(&self) -> bool {
+        *self == ChangeKind::By
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Distribution {
+    pub townsfolk: i32,
+    pub outsiders: i32,
+    pub minions: i32,
+    pub demons: i32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerCount {
+    pub players: usize,
+    pub base: Distribution,
+    /// Every other distribution some combination of setup characters can lead to
+    pub modified: Vec<Distribution>,
+}
+
+impl Distribution {
+    pub const fn new(townsfolk: i32, outsiders: i32, minions: i32, demons: i32) -> Distribution {
+        Distribution {
+            townsfolk,
+            outsiders,
+            minions,
+            demons,
+        }
+    }
+
+    fn count_mut(&mut self, team: &Team) -> Option<&mut i32> {
+        match team {
+            Team::Outsider => Some(&mut self.outsiders),
+            Team::Minion => Some(&mut self.minions),
+            Team::Demon => Some(&mut self.demons),
+            _ => None,
+        }
+    }
+
+    pub fn count(&self, team: &Team) -> i32 {
+        match team {
+            Team::Townsfolk => self.townsfolk,
+            Team::Outsider => self.outsiders,
+            Team::Minion => self.minions,
+            Team::Demon => self.demons,
+            _ => 0,
+        }
+    }
+
+    /// Changes the count of a team, taking the difference from `from`
+    pub fn apply(&mut self, team: &Team, change: i32, from: &Team) {
+        if let Some(count) = self.count_mut(team) {
+            *count += change;
+            match self.count_mut(from) {
+                Some(from) => *from -= change,
+                None => self.townsfolk -= change,
+            }
+        }
+    }
+
+    pub fn players(&self) -> i32 {
+        self.townsfolk + self.outsiders + self.minions + self.demons
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.townsfolk >= 0 && self.outsiders >= 0 && self.minions >= 0 && self.demons >= 0
+    }
+}
+
+impl SetupChange {
+    /// How much choosing `option` changes the count of the team in `distribution`
+    pub fn delta(&self, option: i32, distribution: &Distribution) -> i32 {
+        let count = distribution.count(&self.team);
+        match self.kind {
+            ChangeKind::By => option,
+            ChangeKind::Exactly => option - count,
+            ChangeKind::Majority => distribution.players() / 2 + 1 + option - count,
+        }
+    }
+}
+
+impl SetupModifier {
+    /// The team that makes up the difference when this modifier changes a count
+    pub fn absorbs(&self, change: &SetupChange) -> &Team {
+        if self.bag_disabled && change.team != self.team {
+            &self.team
+        } else {
+            &Team::Townsfolk
+        }
+    }
+}
+
+impl Script {
+    /// Parses the setup modifiers of the characters on the script, looking up named characters
+    /// in `character_list`
+    pub fn resolve_setup(&mut self, character_list: &HashMap<String, Character>) {
+        self.setup_modifiers = self
+            .characters
+            .iter()
+            .filter_map(|character| {
+                let (text, changes) = parse_setup(&character.ability, character_list)?;
+                Some(SetupModifier {
+                    character: character.id.clone(),
+                    team: character.team.clone(),
+                    bag_disabled: character.has_selection("bag-disabled"),
+                    text: text.to_owned(),
+                    changes,
+                })
+            })
+            .collect();
+    }
+
+    pub fn write_setup_json<T>(&self, writer: &mut T) -> io::Result<()>
+    where
+        T: Write,
+    {
+        let out = json!({
+            "modifiers": self.setup_modifiers,
+            "playerCounts": self.player_counts(),
+        });
+
+        serde_json::to_writer(writer, &out)?;
+        Ok(())
+    }
+
+    /// The base distribution for each player count and what the setup characters can change it to
+    pub fn player_counts(&self) -> Vec<PlayerCount> {
+        let modifiers: Vec<_> = self
+            .setup_modifiers
+            .iter()
+            .filter(|modifier| {
+                matches!(
+                    modifier.team,
+                    Team::Townsfolk | Team::Outsider | Team::Minion | Team::Demon
+                )
+            })
+            .collect();
+
+        BASE_DISTRIBUTIONS
+            .iter()
+            .map(|(players, base)| {
+                let mut modified = outcomes(*base, &modifiers);
+                modified.remove(base);
+
+                PlayerCount {
+                    players: *players,
+                    base: *base,
+                    modified: modified.into_iter().collect(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Every valid distribution reached by putting any of `modifiers` in play. Modifiers are added one
+/// at a time over the distinct states reached so far, so the work grows with the outcomes rather
+/// than with every subset of modifiers.
+fn outcomes(base: Distribution, modifiers: &[&SetupModifier]) -> BTreeSet<Distribution> {
+    const TEAMS: [Team; 4] = [Team::Townsfolk, Team::Outsider, Team::Minion, Team::Demon];
+
+    // Chosen modifiers in the bag need a place on their own team, counted alongside
+    let mut states = BTreeSet::from([(base, [0; 4])]);
+    for modifier in modifiers {
+        let mut next = states.clone();
+        for (distribution, needed) in &states {
+            let mut needed = *needed;
+            if !modifier.bag_disabled {
+                if let Some(idx) = TEAMS.iter().position(|team| *team == modifier.team) {
+                    needed[idx] += 1;
+                }
+            }
+            // More chosen characters than players can never fit, however later modifiers change
+            // the counts, so those states are dropped rather than carried along
+            if needed.iter().any(|needed| *needed > distribution.players()) {
+                continue;
+            }
+            for distribution in modifier_outcomes(*distribution, modifier) {
+                next.insert((distribution, needed));
+            }
+        }
+        states = next;
+    }
+
+    states
+        .into_iter()
+        .filter(|(distribution, needed)| {
+            distribution.is_valid()
+                && TEAMS
+                    .iter()
+                    .zip(needed)
+                    .all(|(team, needed)| *needed <= distribution.count(team))
+        })
+        .map(|(distribution, _)| distribution)
+        .collect()
+}
+
+/// The distributions `modifier` can turn `distribution` into, one for each choice of its options
+fn modifier_outcomes(distribution: Distribution, modifier: &SetupModifier) -> Vec<Distribution> {
+    let mut out = vec![];
+    let mut choice = vec![0; modifier.changes.len()];
+
+    loop {
+        let mut modified = distribution;
+        for (change, option) in modifier.changes.iter().zip(&choice) {
+            let delta = change.delta(change.options[*option], &modified);
+            modified.apply(&change.team, delta, modifier.absorbs(change));
+        }
+        out.push(modified);
+
+        // Advance to the next combination of options, like an odometer
+        let mut idx = 0;
+        loop {
+            if idx == choice.len() {
+                return out;
+            }
+            choice[idx] += 1;
+            if choice[idx] < modifier.changes[idx].options.len() {
+                break;
+            }
+            choice[idx] = 0;
+            idx += 1;
+        }
+    }
+}
+
+/// Splits the setup modifier off an ability, returning the bracketed text and what it changes
+pub fn parse_setup<'a>(
+    ability: &'a str,
+    character_list: &HashMap<String, Character>,
+) -> Option<(&'a str, Vec<SetupChange>)> {
+    let start = ability.rfind('[')?;
+    let end = start + ability[start..].find(']')?;
+    let text = &ability[start + 1..end];
+
+    let changes: Vec<_> = text
+        .split('.')
+        .flat_map(|clause| parse_clause(clause.trim(), character_list))
+        .collect();
+
+    if changes.is_empty() {
+        None
+    } else {
+        Some((text, changes))
+    }
+}
+
+fn parse_clause(clause: &str, character_list: &HashMap<String, Character>) -> Vec<SetupChange> {
+    let exactly = |team, options| SetupChange {
+        team,
+        character: None,
+        options,
+        kind: ChangeKind::Exactly,
+        qualifier: String::new(),
+    };
+
+    if let Some(target) = clause.strip_prefix("No ") {
+        if target.to_lowercase().starts_with("evil") {
+            return vec![
+                exactly(Team::Minion, vec![0]),
+                exactly(Team::Demon, vec![0]),
+            ];
+        }
+        let (word, _) = target.split_once(' ').unwrap_or((target, ""));
+        return parse_team(word)
+            .map(|team| exactly(team, vec![0]))
+            .into_iter()
+            .collect();
+    }
+
+    if let Some(target) = clause.strip_prefix("Most players are ") {
+        let change = match parse_team(target) {
+            Some(team) => Some(SetupChange {
+                team,
+                character: None,
+                options: vec![0],
+                kind: ChangeKind::Majority,
+                qualifier: String::new(),
+            }),
+            None => {
+                find_character(target, character_list).map(|(character, qualifier)| SetupChange {
+                    team: character.team.clone(),
+                    character: Some(character.id.clone()),
+                    options: vec![0],
+                    kind: ChangeKind::Majority,
+                    qualifier: qualifier.to_owned(),
+                })
+            }
+        };
+        return change.into_iter().collect();
+    }
+
+    parse_count_clause(clause, character_list)
+        .into_iter()
+        .collect()
+}
+
+/// Parses a clause that changes a count, like `+1 Outsider` or `-1 to +1 Outsiders`
+fn parse_count_clause(
+    clause: &str,
+    character_list: &HashMap<String, Character>,
+) -> Option<SetupChange> {
+    let (first, rest) = parse_count(clause)?;
+    let (options, target) = if let Some(rest) = rest.strip_prefix(" or ") {
+        let (second, rest) = parse_count(rest)?;
+        (vec![first, second], rest)
+    } else if let Some(rest) = rest.strip_prefix(" to ") {
+        let (second, rest) = parse_count(rest)?;
+        ((first.min(second)..=first.max(second)).collect(), rest)
+    } else {
+        (vec![first], rest)
+    };
+
+    let target = target.trim_start();
+    let target = target.strip_prefix("the ").unwrap_or(target);
+    let (word, qualifier) = target.split_once(' ').unwrap_or((target, ""));
+    if let Some(team) = parse_team(word) {
+        let qualifier = qualifier.trim_start_matches("type").trim();
+        return Some(SetupChange {
+            team,
+            character: None,
+            options,
+            kind: ChangeKind::By,
+            qualifier: qualifier.to_owned(),
+        });
+    }
+
+    let (character, qualifier) = find_character(target, character_list)?;
+    Some(SetupChange {
+        team: character.team.clone(),
+        character: Some(character.id.clone()),
+        options,
+        kind: ChangeKind::By,
+        qualifier: qualifier.to_owned(),
+    })
+}
+
+/// Reads a team name, singular or plural
+fn parse_team(word: &str) -> Option<Team> {
+    match word.to_lowercase().trim_end_matches('s') {
+        "townsfolk" => Some(Team::Townsfolk),
+        "outsider" => Some(Team::Outsider),
+        "minion" => Some(Team::Minion),
+        "demon" => Some(Team::Demon),
+        _ => None,
+    }
+}
+
+/// Parses a leading `+1`, `-2` or a bare `+`, which counts as `+1`
+fn parse_count(text: &str) -> Option<(i32, &str)> {
+    let sign = match text.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let digits_end = text[1..]
+        .find(|char: char| !char.is_ascii_digit())
+        .map_or(text.len(), |idx| idx + 1);
+    let digits = &text[1..digits_end];
+    let count = if digits.is_empty() {
+        // Only a bare sign before a name counts, `-?` is up to the storyteller
+        if !text[1..].starts_with(|char: char| char.is_whitespace() || char.is_alphabetic()) {
+            return None;
+        }
+        1
+    } else {
+        digits.parse::<i32>().ok()?
+    };
+
+    Some((sign * count, &text[digits_end..]))
+}
+
+/// Finds the character with the longest name that `target` starts with, allowing a plural `s`
+fn find_character<'a, 'b>(
+    target: &'b str,
+    character_list: &'a HashMap<String, Character>,
+) -> Option<(&'a Character, &'b str)> {
+    let lower = target.to_lowercase();
+    character_list
+        .values()
+        .filter(|character| !character.patched && !character.name.is_empty())
+        .filter_map(|character| {
+            let name = character.name.to_lowercase();
+            let rest = lower.strip_prefix(&name)?;
+            let rest = rest.strip_prefix('s').unwrap_or(rest);
+            if rest.is_empty() || rest.starts_with(' ') {
+                Some((character, name.len()))
+            } else {
+                None
+            }
+        })
+        .max_by(|(a, a_len), (b, b_len)| a_len.cmp(b_len).then_with(|| b.id.cmp(&a.id)))
+        .map(|(character, len)| {
+            let rest = target.get(len..).unwrap_or_default();
+            let rest = rest.strip_prefix('s').unwrap_or(rest);
+            (character, rest.trim())
+        })
+}
+
+fn is_false(b: &bool) -> bool {
+    !b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        character::tests::{character, character_list},
+        diagnostics::Diagnostics,
+    };
+
+    fn characters() -> HashMap<String, Character> {
+        character_list(vec![
+            character("baron", "Baron", "minion", "[+2 Outsiders]"),
+            character("godfather", "Godfather", "minion", "[-1 or +1 Outsider]"),
+            character("lilmonsta", "Lil' Monsta", "demon", "[+1 Minion]"),
+            character("legion", "Legion", "demon", "[Most players are Legion]"),
+            character("villageidiot", "Village Idiot", "townsfolk", ""),
+            character("mercenary", "Mercenary", "traveller", ""),
+            character("chef", "Chef", "townsfolk", ""),
+            character("imp", "Imp", "demon", ""),
+        ])
+    }
+
+    fn parse(ability: &str) -> Vec<SetupChange> {
+        parse_setup(ability, &characters())
+            .map(|(_, changes)| changes)
+            .unwrap_or_default()
+    }
+
+    fn script(ids: &[&str]) -> Script {
+        let mut characters = characters();
+        if let Some(lilmonsta) = characters.get_mut("lilmonsta") {
+            lilmonsta.special = Some(serde_json::json!([
+                { "type": "selection", "name": "bag-disabled" }
+            ]));
+        }
+        let text = format!("Test\nTester\n\n{}", ids.join("\n"));
+        let mut diagnostics = Diagnostics::default();
+        let mut script = Script::parse("test", &text, &characters, &mut diagnostics).unwrap();
+        script.resolve_setup(&characters);
+        script
+    }
+
+    #[test]
+    fn parses_team_changes() {
+        let changes = parse("You start knowing things. [+1 Outsider]");
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].team, Team::Outsider);
+        assert_eq!(changes[0].options, vec![1]);
+        assert_eq!(changes[0].kind, ChangeKind::By);
+
+        assert_eq!(parse("[-1 or +1 Outsider]")[0].options, vec![-1, 1]);
+        assert_eq!(parse("[+0 to +2 Outsiders]")[0].options, vec![0, 1, 2]);
+        assert_eq!(parse("[-1 Minion. +1 Demon type]")[1].team, Team::Demon);
+    }
+
+    #[test]
+    fn parses_character_changes() {
+        let changes = parse("[+1 Mercenary of opposing alignment]");
+        assert_eq!(changes[0].character.as_deref(), Some("mercenary"));
+        assert_eq!(changes[0].team, Team::Traveller);
+        assert_eq!(changes[0].qualifier, "of opposing alignment");
+
+        let changes = parse("[+0 to +2 Village Idiots. 1 of the extras is drunk]");
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].character.as_deref(), Some("villageidiot"));
+        assert_eq!(changes[0].options, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn parses_exact_counts() {
+        let changes = parse("[No evil characters]");
+        assert_eq!(changes.len(), 2);
+        assert!(changes
+            .iter()
+            .all(|change| change.kind == ChangeKind::Exactly && change.options == vec![0]));
+        assert_eq!(parse("[No Demon]")[0].team, Team::Demon);
+
+        let changes = parse("[Most players are Legion]");
+        assert_eq!(changes[0].kind, ChangeKind::Majority);
+        assert_eq!(changes[0].character.as_deref(), Some("legion"));
+    }
+
+    #[test]
+    fn skips_text_without_counts() {
+        assert!(parse("[1 Townsfolk is evil]").is_empty());
+        assert!(parse("[-? to +? Outsiders]").is_empty());
+        assert!(parse("[You neighbor the Demon]").is_empty());
+        assert!(parse("No brackets, +1 Outsider").is_empty());
+    }
+
+    #[test]
+    fn deltas_follow_the_kind() {
+        let base = Distribution::new(5, 1, 1, 1);
+        let no_demon = &parse("[No Demon]")[0];
+        assert_eq!(no_demon.delta(0, &base), -1);
+        let legion = &parse("[Most players are Legion]")[0];
+        assert_eq!(legion.delta(0, &base), 4);
+    }
+
+    #[test]
+    fn table_has_every_player_count() {
+        let counts = script(&["chef", "imp"]).player_counts();
+        assert_eq!(counts.len(), 11);
+        assert_eq!(counts[0].players, 5);
+        assert_eq!(counts[0].base, Distribution::new(3, 0, 1, 1));
+        assert!(counts.iter().all(|count| count.modified.is_empty()));
+    }
+
+    #[test]
+    fn table_combines_modifiers() {
+        let counts = script(&["chef", "baron", "godfather", "imp"]).player_counts();
+        let seven = &counts[2].modified;
+        // Baron and Godfather alone, but only together once there are two Minion slots
+        assert!(seven.contains(&Distribution::new(3, 2, 1, 1)));
+        assert!(seven.contains(&Distribution::new(4, 1, 1, 1)));
+        assert!(!seven.contains(&Distribution::new(2, 3, 1, 1)));
+        assert!(counts[5].modified.contains(&Distribution::new(4, 3, 2, 1)));
+    }
+
+    #[test]
+    fn bag_disabled_modifiers_fill_their_own_slot() {
+        let counts = script(&["chef", "lilmonsta"]).player_counts();
+        assert_eq!(counts[0].modified, vec![Distribution::new(3, 0, 2, 0)]);
+    }
+
+    #[test]
+    fn table_handles_many_modifiers() {
+        let mut script = script(&["chef", "godfather", "imp"]);
+        let godfather = script.setup_modifiers[0].clone();
+        script.setup_modifiers = vec![godfather; 70];
+        let counts = script.player_counts();
+        // However many Godfathers there are, only one fits in a single Minion slot
+        assert_eq!(counts[2].modified, vec![Distribution::new(4, 1, 1, 1)]);
+    }
+}
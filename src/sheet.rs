@@ -0,0 +1,133 @@
+use std::io::{self, Write};
+
+use crate::{
+    almanac::write_icon,
+    character::Team,
+    markup::{escape, render},
+    script::Script,
+};
+
+const SHEET_STYLE: &str = include_str!("sheet_style.css");
+
+static SHEET_TEAMS: [Team; 6] = [
+    Team::Townsfolk,
+    Team::Outsider,
+    Team::Minion,
+    Team::Demon,
+    Team::Traveller,
+    Team::Fabled,
+];
+
+#[derive(Debug, Clone, Copy)]
+pub enum Paper {
+    A4,
+    Letter,
+}
+
+impl Paper {
+    pub const ALL: [Paper; 2] = [Paper::A4, Paper::Letter];
+
+    pub fn name(&self) -> &str {
+        match self {
+            Paper::A4 => "a4",
+            Paper::Letter => "letter",
+        }
+    }
+
+    pub fn page_rule(&self) -> &str {
+        match self {
+            Paper::A4 => "@page { size: A4; margin: 8mm; } .sheet { width: 194mm; }",
+            Paper::Letter => "@page { size: letter; margin: 8mm; } .sheet { width: 199.9mm; }",
+        }
+    }
+}
+
+impl Script {
+    pub fn write_sheet<T>(&self, writer: &mut T, paper: Paper) -> io::Result<()>
+    where
+        T: Write,
+    {
+        write!(
+            writer,
+            "<!DOCTYPE html><html lang=\"en\"><head><meta charset=\"UTF-8\"><meta name=\"viewport\" content=\"width=device-width, initial-scale=1.0\"><title>{}</title><style>{SHEET_STYLE}{}</style></head><body><div class=\"sheet\">",
+            escape(&self.name),
+            paper.page_rule()
+        )
+        ?;
+
+        write!(
+            writer,
+            "<header><h1>{}</h1><p class=\"author\">by {}</p></header>",
+            escape(&self.name),
+            escape(&self.author)
+        )?;
+
+        for team in &SHEET_TEAMS {
+            let characters: Vec<_> = self
+                .characters
+                .iter()
+                .filter(|character| character.team == *team)
+                .collect();
+            if characters.is_empty() {
+                continue;
+            }
+
+            write!(
+                writer,
+                "<section class=\"{0}\"><h2>{0}</h2><div class=\"sheet-characters\">",
+                team.to_str()
+            )?;
+            for character in characters {
+                write!(writer, "<div class=\"sheet-character\">")?;
+                write_icon(writer, character)?;
+                write!(
+                    writer,
+                    "<div><h3>{}</h3><p>{}</p></div></div>",
+                    escape(&character.name),
+                    render(&character.ability)
+                )?;
+            }
+            write!(writer, "</div></section>")?;
+        }
+
+        self.write_sheet_footer(writer)?;
+
+        write!(writer, "</div></body></html>")?;
+
+        Ok(())
+    }
+
+    fn write_sheet_footer<T>(&self, writer: &mut T) -> io::Result<()>
+    where
+        T: Write,
+    {
+        if !self.has_jinxes_or_rules() {
+            return Ok(());
+        }
+
+        write!(writer, "<footer>")?;
+        for rule in &self.bootlegger_rules {
+            write!(
+                writer,
+                "<div class=\"sheet-jinx\"><p>{}</p></div>",
+                render(rule)
+            )?;
+        }
+        for rule in self.character_rules() {
+            write!(writer, "<div class=\"sheet-jinx\">")?;
+            write_icon(writer, rule.character)?;
+            write!(writer, "<p>{}</p></div>", render(rule.reason))?;
+        }
+        for jinx in self.jinx_pairs() {
+            for reason in jinx.reasons() {
+                write!(writer, "<div class=\"sheet-jinx\">")?;
+                write_icon(writer, jinx.first)?;
+                write_icon(writer, jinx.second)?;
+                write!(writer, "<p>{}</p></div>", render(reason))?;
+            }
+        }
+        write!(writer, "</footer>")?;
+
+        Ok(())
+    }
+}
@@ -1,8 +1,9 @@
-use std::{fs::File, io::Read};
+use std::collections::HashMap;
 
-use serde::Deserialize;
-
-use crate::character::{Character, Team};
+use crate::{
+    character::{Character, Team},
+    night_order::{Night, NightOrder},
+};
 
 pub struct SpecialCharacters {
     pub dusk: Character,
@@ -11,32 +12,39 @@ pub struct SpecialCharacters {
     pub dawn: Character,
 }
 
-#[derive(Debug, Deserialize)]
-struct NightOrder {
-    #[serde(rename = "firstNight")]
-    first_night: Vec<String>,
-    #[serde(rename = "otherNight")]
-    other_night: Vec<String>,
-}
-
-pub fn special_characters() -> SpecialCharacters {
-    let mut night_order_str = String::new();
-    File::open("night-order.json")
-        .expect("Night order not found")
-        .read_to_string(&mut night_order_str)
-        .expect("Failed to read night order file");
-    let night_order = serde_json::from_str::<NightOrder>(&night_order_str)
-        .expect("Failed to parse night order json");
-
+pub fn special_characters(
+    night_order: &NightOrder,
+    character_list: &HashMap<String, Character>,
+) -> SpecialCharacters {
     SpecialCharacters {
-        dusk: special_character("DUSK", "Dusk", &night_order),
-        minions: special_character("MINION", "Minion Info", &night_order),
-        demon: special_character("DEMON", "Demon Info", &night_order),
-        dawn: special_character("DAWN", "Dawn", &night_order),
+        dusk: special_character("DUSK", "Dusk", "dusk", night_order, character_list),
+        minions: special_character(
+            "MINION",
+            "Minion Info",
+            "minioninfo",
+            night_order,
+            character_list,
+        ),
+        demon: special_character(
+            "DEMON",
+            "Demon Info",
+            "demoninfo",
+            night_order,
+            character_list,
+        ),
+        dawn: special_character("DAWN", "Dawn", "dawn", night_order, character_list),
     }
 }
 
-fn special_character(id: &str, name: &str, night_order: &NightOrder) -> Character {
+fn special_character(
+    id: &str,
+    name: &str,
+    official_id: &str,
+    night_order: &NightOrder,
+    character_list: &HashMap<String, Character>,
+) -> Character {
+    let official = character_list.get(official_id);
+
     Character {
         id: String::from(id),
         name: String::from(name),
@@ -44,20 +52,14 @@ fn special_character(id: &str, name: &str, night_order: &NightOrder) -> Characte
         ability: String::new(),
         reminders: vec![],
         reminders_global: vec![],
-        first_night_reminder: String::new(),
-        other_night_reminder: String::new(),
-        first_night: night_order
-            .first_night
-            .iter()
-            .position(|other| other == id)
-            .map(|pos| pos + 1)
-            .unwrap_or(0) as f32,
-        other_night: night_order
-            .other_night
-            .iter()
-            .position(|other| other == id)
-            .map(|pos| pos + 1)
-            .unwrap_or(0) as f32,
+        first_night_reminder: official
+            .map(|official| official.first_night_reminder.clone())
+            .unwrap_or_default(),
+        other_night_reminder: official
+            .map(|official| official.other_night_reminder.clone())
+            .unwrap_or_default(),
+        first_night: night_order.position(Night::First, id).unwrap_or_default(),
+        other_night: night_order.position(Night::Other, id).unwrap_or_default(),
         setup: false,
         official: true,
         patched: false,
@@ -68,9 +70,12 @@ fn special_character(id: &str, name: &str, night_order: &NightOrder) -> Characte
         how_to_run: vec![],
         advice: vec![],
         attribution: vec![],
-        image: vec![],
+        image: official
+            .map(|official| official.image.clone())
+            .unwrap_or_default(),
         special: None,
         jinxes: vec![],
         required_characters: vec![],
+        wakes: vec![],
     }
 }
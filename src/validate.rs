@@ -0,0 +1,253 @@
+use std::{fs::File, io::Read};
+
+use serde_json::Value;
+
+use crate::diagnostics::Diagnostics;
+
+struct SchemaError {
+    path: Vec<String>,
+    message: String,
+}
+
+pub fn read_schema(path: &str, diagnostics: &mut Diagnostics) -> Option<Value> {
+    read_json(path, diagnostics)
+}
+
+pub fn validate(source: &str, schema: &Value, diagnostics: &mut Diagnostics) {
+    let Some(script) = read_json(source, diagnostics) else {
+        return;
+    };
+
+    let mut errors = vec![];
+    check(schema, &script, &mut vec![], &mut errors);
+
+    for error in errors {
+        let mut path = error.path.iter();
+        let label = match path.next() {
+            Some(idx) => match idx.parse().ok().and_then(|idx: usize| script.get(idx)) {
+                Some(Value::String(id)) => format!("character {id}"),
+                Some(Value::Object(map)) => match map.get("id").and_then(Value::as_str) {
+                    Some("_meta") => String::from("_meta"),
+                    Some(id) => format!("character {id}"),
+                    None => format!("entry {idx}"),
+                },
+                _ => format!("entry {idx}"),
+            },
+            None => String::from("script"),
+        };
+        let rest: Vec<_> = path.map(String::as_str).collect();
+
+        if rest.is_empty() {
+            diagnostics.error(source, 0, 0, format!("{label}: {}", error.message));
+        } else {
+            diagnostics.error(
+                source,
+                0,
+                0,
+                format!("{label}: {}: {}", rest.join("."), error.message),
+            );
+        }
+    }
+}
+
+fn read_json(path: &str, diagnostics: &mut Diagnostics) -> Option<Value> {
+    let mut buf = String::new();
+    if let Err(err) = File::open(path).and_then(|mut file| file.read_to_string(&mut buf)) {
+        diagnostics.error(path, 0, 0, format!("Failed to read file: {err}"));
+        return None;
+    }
+
+    match serde_json::from_str(&buf) {
+        Ok(value) => Some(value),
+        Err(err) => {
+            diagnostics.error(path, err.line(), err.column(), err.to_string());
+            None
+        }
+    }
+}
+
+fn check(schema: &Value, value: &Value, path: &mut Vec<String>, errors: &mut Vec<SchemaError>) {
+    let Some(schema) = schema.as_object() else {
+        return;
+    };
+    if let Some(kind) = schema.get("type").and_then(Value::as_str) {
+        if !matches_type(kind, value) {
+            push_error(
+                errors,
+                path,
+                format!("expected {kind}, found {}", type_name(value)),
+            );
+            return;
+        }
+    }
+
+    if let Some(Value::Array(options)) = schema.get("enum") {
+        if !options.contains(value) {
+            let options: Vec<_> = options.iter().map(Value::to_string).collect();
+            push_error(
+                errors,
+                path,
+                format!("{value} is not one of {}", options.join(", ")),
+            );
+        }
+    }
+
+    if let Value::String(str) = value {
+        let len = str.chars().count();
+        if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+            if len as u64 > max {
+                push_error(
+                    errors,
+                    path,
+                    format!("is {len} characters long, the maximum is {max}"),
+                );
+            }
+        }
+        if schema.get("format").and_then(Value::as_str) == Some("uri") && !is_uri(str) {
+            push_error(errors, path, format!("{str} is not a valid uri"));
+        }
+    }
+
+    if let Value::Array(items) = value {
+        let len = items.len() as u64;
+        if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+            if len < min {
+                push_error(
+                    errors,
+                    path,
+                    format!("has {len} items, the minimum is {min}"),
+                );
+            }
+        }
+        if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+            if len > max {
+                push_error(
+                    errors,
+                    path,
+                    format!("has {len} items, the maximum is {max}"),
+                );
+            }
+        }
+        if let Some(item_schema) = schema.get("items") {
+            for (idx, item) in items.iter().enumerate() {
+                path.push(idx.to_string());
+                check(item_schema, item, path, errors);
+                path.pop();
+            }
+        }
+    }
+
+    if let Value::Object(map) = value {
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for key in required.iter().filter_map(Value::as_str) {
+                if !map.contains_key(key) {
+                    push_error(errors, path, format!("missing required property {key}"));
+                }
+            }
+        }
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for (key, property) in map {
+            match properties.and_then(|properties| properties.get(key)) {
+                Some(property_schema) => {
+                    path.push(key.to_owned());
+                    check(property_schema, property, path, errors);
+                    path.pop();
+                }
+                None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                    push_error(errors, path, format!("unknown property {key}"));
+                }
+                None => (),
+            }
+        }
+    }
+
+    if let Some(Value::Array(branches)) = schema.get("oneOf") {
+        let results: Vec<_> = branches
+            .iter()
+            .map(|branch| {
+                let mut branch_errors = vec![];
+                check(branch, value, path, &mut branch_errors);
+                branch_errors
+            })
+            .collect();
+
+        match results.iter().filter(|result| result.is_empty()).count() {
+            1 => (),
+            0 => {
+                // Report the branch that came closest to matching, as that is most likely the intended one
+                if let Some((_, closest)) = branches
+                    .iter()
+                    .zip(results)
+                    .min_by_key(|(branch, errors)| branch_rank(branch, value, errors.len()))
+                {
+                    errors.extend(closest);
+                }
+            }
+            _ => push_error(errors, path, String::from("matches more than one schema")),
+        }
+    }
+}
+
+fn branch_rank(branch: &Value, value: &Value, error_count: usize) -> (bool, usize, usize) {
+    let type_mismatch = branch
+        .get("type")
+        .and_then(Value::as_str)
+        .is_some_and(|kind| !matches_type(kind, value));
+
+    // A property restricted to fixed values, like the `_meta` id, marks which branch was meant
+    let discriminator_mismatches = match (branch.get("properties"), value) {
+        (Some(Value::Object(properties)), Value::Object(map)) => properties
+            .iter()
+            .filter(
+                |(key, property)| match (property.get("enum"), map.get(*key)) {
+                    (Some(Value::Array(options)), Some(value)) => !options.contains(value),
+                    _ => false,
+                },
+            )
+            .count(),
+        _ => 0,
+    };
+
+    (type_mismatch, discriminator_mismatches, error_count)
+}
+
+fn push_error(errors: &mut Vec<SchemaError>, path: &[String], message: String) {
+    errors.push(SchemaError {
+        path: path.to_vec(),
+        message,
+    });
+}
+
+fn matches_type(kind: &str, value: &Value) -> bool {
+    match kind {
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn is_uri(str: &str) -> bool {
+    str.split_once(':').is_some_and(|(scheme, rest)| {
+        !rest.is_empty()
+            && scheme.starts_with(|char: char| char.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|char| char.is_ascii_alphanumeric() || matches!(char, '+' | '-' | '.'))
+    })
+}